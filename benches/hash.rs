@@ -1,3 +1,7 @@
+#![cfg(all(
+    any(target_vendor = "apple", feature = "gnustep"),
+    not(feature = "mock")
+))]
 #![allow(soft_unstable)]
 #![feature(test)]
 
@@ -11,10 +15,10 @@ pub fn hash(b: &mut test::Bencher) {
     unsafe {
         let data = "aaaaa";
         let obj = nsobj_alloc(class!(NSData));
-        let obj = nsdata_init_with_bytes(obj, data.as_ptr() as _, data.len() as _);
+        let obj = nsdata_init_with_bytes(obj, data.as_ptr() as _, data.len() as _).unwrap();
         b.iter(|| {
             for _ in 0..1_000_000 {
-                nsobj_hash(obj);
+                nsobj_hash(&*obj);
             }
         });
     }
@@ -0,0 +1,38 @@
+fn main() {
+    #[cfg(feature = "exception")]
+    compile_exception_trampoline();
+    let mock = std::env::var_os("CARGO_FEATURE_MOCK").is_some();
+    let gnustep = std::env::var_os("CARGO_FEATURE_GNUSTEP").is_some();
+    if mock && !gnustep && std::env::var("CARGO_CFG_TARGET_VENDOR").as_deref() != Ok("apple") {
+        stub_libobjc();
+    }
+}
+
+/// Puts an empty `libobjc` on the link path. `objc` links it unconditionally,
+/// though mock builds never call into it.
+fn stub_libobjc() {
+    let dir = std::path::Path::new(&std::env::var_os("OUT_DIR").unwrap()).join("objc-stub");
+    std::fs::create_dir_all(&dir).unwrap();
+    for name in &["libobjc.a", "objc.lib"] {
+        std::fs::write(dir.join(name), b"!<arch>\n").unwrap();
+    }
+    println!("cargo:rustc-link-search=native={}", dir.display());
+}
+
+/// Builds the `@try`/`@catch` trampoline behind `objc_util::exception`. The
+/// mock runtime catches its exceptions in Rust and doesn't need it.
+#[cfg(feature = "exception")]
+fn compile_exception_trampoline() {
+    println!("cargo:rerun-if-changed=src/exception.m");
+    if std::env::var_os("CARGO_FEATURE_MOCK").is_some() {
+        return;
+    }
+    let mut build = cc::Build::new();
+    build.file("src/exception.m").flag("-fobjc-exceptions");
+    if std::env::var("CARGO_CFG_TARGET_VENDOR").as_deref() != Ok("apple") {
+        // Only clang has this flag. gcc would fail on it, and builds for its
+        // own GNU runtime ABI instead; see `exception`'s docs.
+        build.flag_if_supported("-fobjc-runtime=gnustep-2.0");
+    }
+    build.compile("objc_util_exception");
+}
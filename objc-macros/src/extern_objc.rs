@@ -1,7 +1,10 @@
 use crate::msg_wrappers::MsgWrappers;
 use quote::ToTokens;
 use std::convert::TryInto as _;
-use syn::parse::{self, Parse, ParseStream};
+use syn::{
+    parse::{self, Parse, ParseStream},
+    spanned::Spanned,
+};
 
 pub struct ExternObjc {
     msg_wrappers: MsgWrappers,
@@ -9,13 +12,23 @@ pub struct ExternObjc {
 
 impl Parse for ExternObjc {
     fn parse(input: ParseStream) -> parse::Result<Self> {
-        let block = input.parse::<syn::ItemForeignMod>()?;
-        if let Some(name) = block.abi.name {
-            if name.value() != "ObjC" {
-                return Err(syn::Error::new(name.span(), "Expected `ObjC`"));
+        let msg_wrappers = match input.parse::<syn::Item>()? {
+            syn::Item::ForeignMod(block) => {
+                if let Some(name) = block.abi.name {
+                    if name.value() != "ObjC" {
+                        return Err(syn::Error::new(name.span(), "Expected `ObjC`"));
+                    }
+                }
+                block.items.try_into()?
             }
-        }
-        let msg_wrappers = block.items.try_into()?;
+            syn::Item::Impl(block) => block.try_into()?,
+            item => {
+                return Err(syn::Error::new(
+                    item.span(),
+                    "Expected an `extern \"ObjC\"` or `impl` block",
+                ))
+            }
+        };
         Ok(ExternObjc { msg_wrappers })
     }
 }
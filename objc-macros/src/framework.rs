@@ -17,12 +17,44 @@ impl Parse for Framework {
     }
 }
 
+impl Framework {
+    /// The GNUstep library standing in for an Apple framework, if there is one,
+    /// along with a function it exports. Classes are only ever looked up by
+    /// name, so the function is referenced to stop `--as-needed` from dropping
+    /// the library.
+    fn gnustep_library(&self) -> Option<(&'static str, &'static str)> {
+        match self.name.value().as_str() {
+            "Foundation" => Some(("gnustep-base", "NSPageSize")),
+            "AppKit" => Some(("gnustep-gui", "NSApplicationMain")),
+            "CoreFoundation" => Some(("gnustep-corebase", "CFRetain")),
+            _ => None,
+        }
+    }
+}
+
 impl ToTokens for Framework {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
         let name = &self.name;
         tokens.extend(quote::quote! {
-            #[link(name = #name, kind = "framework")]
+            #[cfg_attr(target_vendor = "apple", link(name = #name, kind = "framework"))]
             extern "C" {}
-        })
+        });
+        if cfg!(feature = "gnustep") {
+            if let Some((library, anchor)) = self.gnustep_library() {
+                tokens.extend(quote::quote! {
+                    #[cfg(not(target_vendor = "apple"))]
+                    const _: () = {
+                        #[link(name = #library, kind = "dylib")]
+                        extern "C" {
+                            #[link_name = #anchor]
+                            fn anchor();
+                        }
+
+                        #[used]
+                        static ANCHOR: unsafe extern "C" fn() = anchor;
+                    };
+                })
+            }
+        }
     }
 }
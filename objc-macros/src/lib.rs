@@ -6,25 +6,18 @@ mod msg_wrappers;
 mod objc_attr;
 mod objc_selector;
 mod os_versions;
+mod symbol;
 
-use crate::{extern_objc::ExternObjc, framework::Framework};
+use crate::{extern_objc::ExternObjc, framework::Framework, os_versions::OSVersions};
 use proc_macro::TokenStream;
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher as _},
-};
+use std::convert::TryFrom;
 use syn::{
     parse::{self, Parse, ParseStream},
     parse_macro_input,
+    punctuated::Punctuated,
     spanned::Spanned,
 };
 
-pub(crate) fn calculate_hash<T: Hash>(t: &T) -> u64 {
-    let mut s = DefaultHasher::new();
-    t.hash(&mut s);
-    s.finish()
-}
-
 pub(crate) fn assign_group_to_span(
     stream: proc_macro2::TokenStream,
     span: proc_macro2::Span,
@@ -38,6 +31,27 @@ pub(crate) fn assign_group_to_span(
         .collect()
 }
 
+/// A block evaluating to `mock` in crates with their `mock` feature enabled,
+/// and to `real` elsewhere. The crate using the macro decides, so a `mock`
+/// feature enabled elsewhere in the dependency graph doesn't leak into it.
+pub(crate) fn mock_or_real(
+    mock: proc_macro2::TokenStream,
+    real: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    quote::quote! {
+        {
+            #[cfg(feature = "mock")]
+            {
+                #mock
+            }
+            #[cfg(not(feature = "mock"))]
+            {
+                #real
+            }
+        }
+    }
+}
+
 struct OSSupports {
     supports_func: syn::Path,
 }
@@ -45,7 +59,7 @@ struct OSSupports {
 impl Parse for OSSupports {
     fn parse(input: ParseStream) -> parse::Result<Self> {
         let mut supports_func: syn::Path = input.parse()?;
-        let mut last = supports_func.segments.last_mut().unwrap();
+        let last = supports_func.segments.last_mut().unwrap();
         last.ident = syn::Ident::new(&format!("_supports_{}", last.ident), last.ident.span());
         Ok(Self { supports_func })
     }
@@ -72,7 +86,7 @@ struct SelectorFunc {
 impl Parse for SelectorFunc {
     fn parse(input: ParseStream) -> parse::Result<Self> {
         let mut sel_func: syn::Path = input.parse()?;
-        let mut last = sel_func.segments.last_mut().unwrap();
+        let last = sel_func.segments.last_mut().unwrap();
         last.ident = syn::Ident::new(&format!("_sel_{}", last.ident), last.ident.span());
         Ok(Self { sel_func })
     }
@@ -92,67 +106,341 @@ pub fn sel_impl(input: TokenStream) -> TokenStream {
     parsed.into()
 }
 
-#[cfg(feature = "compile-time")]
+/// How class and selector references are resolved.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Lookup {
+    /// By name, through the runtime, on first use.
+    Runtime,
+    /// Through references in the Mach-O `__objc_*` sections, which dyld fixes
+    /// up at load time.
+    Static,
+}
+
+impl Default for Lookup {
+    /// The `compile-time` feature makes lookups static unless a binding
+    /// asks otherwise.
+    fn default() -> Self {
+        if cfg!(feature = "compile-time") {
+            Lookup::Static
+        } else {
+            Lookup::Runtime
+        }
+    }
+}
+
+impl Lookup {
+    /// Parses `"runtime"` or `"static"`.
+    pub(crate) fn from_lit(lit: &syn::Lit) -> parse::Result<Self> {
+        match lit {
+            syn::Lit::Str(s) if s.value() == "runtime" => Ok(Lookup::Runtime),
+            syn::Lit::Str(s) if s.value() == "static" => Ok(Lookup::Static),
+            _ => Err(syn::Error::new(
+                lit.span(),
+                "Expected `\"runtime\"` or `\"static\"`",
+            )),
+        }
+    }
+
+    /// `static_body` for static lookups, unless they can't work here. Mock
+    /// builds never get that far, so the error is left to the expansion.
+    pub(crate) fn static_or_error(
+        static_body: proc_macro2::TokenStream,
+        span: proc_macro2::Span,
+    ) -> proc_macro2::TokenStream {
+        if cfg!(feature = "gnustep") {
+            quote::quote_spanned! {span=>
+                compile_error!("Static lookup emits Mach-O sections and requires an Apple target")
+            }
+        } else {
+            static_body
+        }
+    }
+}
+
+/// `NSData`, `runtime NSData` or `static NSData`, optionally followed by
+/// availability keys: `NSData, ios = "14"`.
+struct ClassArg {
+    lookup: Lookup,
+    static_token: Option<syn::Token![static]>,
+    class: syn::Ident,
+    versions: Option<OSVersions>,
+}
+
+impl Parse for ClassArg {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let static_token: Option<syn::Token![static]> = input.parse()?;
+        let lookup = if static_token.is_some() {
+            Lookup::Static
+        } else if input.peek(syn::Ident) && input.peek2(syn::Ident) {
+            let keyword: syn::Ident = input.parse()?;
+            if keyword != "runtime" {
+                return Err(syn::Error::new(
+                    keyword.span(),
+                    "Expected `runtime` or `static`",
+                ));
+            }
+            Lookup::Runtime
+        } else {
+            Lookup::default()
+        };
+        let class = input.parse()?;
+        let versions = if input.is_empty() {
+            None
+        } else {
+            let comma: syn::Token![,] = input.parse()?;
+            let keys = Punctuated::<syn::NestedMeta, syn::Token![,]>::parse_terminated(input)?;
+            if keys.is_empty() {
+                return Err(syn::Error::new(comma.span, "Expected availability keys"));
+            }
+            Some(OSVersions::try_from(keys.into_iter())?)
+        };
+        let arg = Self {
+            lookup,
+            static_token,
+            class,
+            versions,
+        };
+        if arg.versions.is_some() {
+            arg.check_optional()?;
+        }
+        Ok(arg)
+    }
+}
+
+impl ClassArg {
+    /// Optional classes are looked up by name: a static reference would make
+    /// a missing class fail the whole binary at load time.
+    fn check_optional(&self) -> parse::Result<()> {
+        match self.static_token {
+            Some(token) => Err(syn::Error::new(
+                token.span,
+                "Optional classes are always looked up at runtime",
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A `ClassArg` for `class_opt!`.
+struct OptClassArg(ClassArg);
+
+impl Parse for OptClassArg {
+    fn parse(input: ParseStream) -> parse::Result<Self> {
+        let arg: ClassArg = input.parse()?;
+        arg.check_optional()?;
+        Ok(Self(arg))
+    }
+}
+
 #[proc_macro]
 pub fn class_impl(input: TokenStream) -> TokenStream {
-    let class: syn::Ident = parse_macro_input!(input);
-    let random_id = &format!(
-        "{}",
-        calculate_hash(&format!("{}{:?}", class, class.span()))
-    );
+    let ClassArg {
+        lookup,
+        class,
+        versions,
+        ..
+    } = parse_macro_input!(input);
+    if let Some(versions) = versions {
+        return class_opt_func(&class, Some(&versions)).into();
+    }
+    let body = class_func_body(&class, lookup);
+    let parsed = quote::quote! {
+        fn proc_macro_support_wrapper() -> &'static objc_util::runtime::Class {
+            #body
+        }
+    };
+    parsed.into()
+}
+
+#[proc_macro]
+pub fn class_opt_impl(input: TokenStream) -> TokenStream {
+    let OptClassArg(ClassArg { class, versions, .. }) = parse_macro_input!(input);
+    class_opt_func(&class, versions.as_ref()).into()
+}
+
+/// The wrapper for `class_opt!`, and for `class!` with availability keys:
+/// `None` when `versions` doesn't cover the running OS or the class isn't
+/// there.
+fn class_opt_func(class: &syn::Ident, versions: Option<&OSVersions>) -> proc_macro2::TokenStream {
+    let body = class_opt_func_body(class);
+    let body = match versions {
+        Some(versions) => {
+            let supported = versions.supported_check();
+            quote::quote! {
+                let supported = #supported;
+                if supported {
+                    #body
+                } else {
+                    None
+                }
+            }
+        }
+        None => body,
+    };
+    quote::quote! {
+        fn proc_macro_support_wrapper() -> Option<&'static objc_util::runtime::Class> {
+            #body
+        }
+    }
+}
+
+/// A block evaluating to the `&'static Class` named `class`.
+pub(crate) fn class_func_body(class: &syn::Ident, lookup: Lookup) -> proc_macro2::TokenStream {
+    class_ref_func_body(class, ClassRef::Class, lookup)
+}
+
+/// Like `class_func_body`, but `None` instead of a crash when the class
+/// doesn't exist.
+fn class_opt_func_body(class: &syn::Ident) -> proc_macro2::TokenStream {
+    let class_string = syn::LitStr::new(&class.to_string(), class.span());
+    mock_or_real(
+        quote::quote! {
+            objc_util::mock::class_opt(#class_string)
+        },
+        quote::quote! {
+            objc_util::runtime::Class::get(#class_string)
+        },
+    )
+}
+
+/// Like `class_func_body`, for the superclass a message to `super` starts
+/// its lookup at.
+pub(crate) fn superclass_func_body(
+    class: &syn::Ident,
+    lookup: Lookup,
+) -> proc_macro2::TokenStream {
+    class_ref_func_body(class, ClassRef::Super, lookup)
+}
+
+fn class_ref_func_body(
+    class: &syn::Ident,
+    class_ref: ClassRef,
+    lookup: Lookup,
+) -> proc_macro2::TokenStream {
+    let class_string = syn::LitStr::new(&class.to_string(), class.span());
+    let real = match lookup {
+        Lookup::Static => Lookup::static_or_error(
+            static_class_body(class, class_ref, |var| std::env::var(var).ok()),
+            class.span(),
+        ),
+        Lookup::Runtime => quote::quote! {
+            objc_util::objc::class!(#class)
+        },
+    };
+    mock_or_real(
+        quote::quote! {
+            objc_util::mock::class(#class_string)
+        },
+        real,
+    )
+}
+
+/// The section a static class reference lives in.
+enum ClassRef {
+    /// `__objc_classrefs`, for messages to the class.
+    Class,
+    /// `__objc_superrefs`, for messages to `super`.
+    Super,
+}
+
+/// `env` looks up the deployment target variables, like
+/// `os_versions::deployment_target_cfg`.
+fn static_class_body(
+    class: &syn::Ident,
+    class_ref: ClassRef,
+    env: impl Fn(&str) -> Option<String>,
+) -> proc_macro2::TokenStream {
+    let (section, ref_prefix, kind) = match class_ref {
+        ClassRef::Class => (
+            "__DATA,__objc_classrefs,regular,no_dead_strip",
+            "\x01L_OBJC_CLASSLIST_REFERENCES_$_.",
+            "class",
+        ),
+        ClassRef::Super => (
+            "__DATA,__objc_superrefs,regular,no_dead_strip",
+            "\x01L_OBJC_CLASSLIST_SUP_REFS_$_.",
+            "superclass",
+        ),
+    };
     let class_string = format!("{}", class);
+    let symbol_id = &symbol::symbol_id(kind, &class_string, class.span());
 
     let class_name = ["\x01_OBJC_CLASS_$_", &class_string].concat();
 
     let class_ref_export_name = [
-        "\x01L_OBJC_CLASSLIST_REFERENCES_$_.",
-        random_id,
+        ref_prefix,
+        symbol_id,
         ".",
         &class_string,
     ]
     .concat();
 
-    let parsed = assign_group_to_span(
+    // `objc_opt_class` realizes and initializes the class without a message
+    // send, but older runtimes don't export it. They get `+class` instead, as
+    // does any build without a deployment target in the environment, since
+    // rustc's default target may predate `objc_opt_class`.
+    let opt_class_cfg = os_versions::deployment_target_cfg(
+        &[
+            ("macos", "10.15"),
+            ("ios", "13"),
+            ("maccatalyst", "13"),
+            ("tvos", "13"),
+            ("watchos", "6"),
+            ("visionos", "1"),
+        ],
+        env,
+    );
+
+    assign_group_to_span(
         quote::quote! {
-            fn proc_macro_support_wrapper() -> &'static objc_util::runtime::Class {
-                #[link(name = "objc", kind = "dylib")]
-                extern {
-                    fn objc_opt_class(name: *const std::os::raw::c_void) -> *mut std::os::raw::c_void;
-                }
+            {
                 extern {
                     #[link_name = #class_name]
                     static CLASS_NAME: u8;
                 }
 
-                #[link_section = "__DATA,__objc_classrefs,regular,no_dead_strip"]
+                #[link_section = #section]
                 #[export_name = #class_ref_export_name]
                 static CLASS_REF: &'static u8 = unsafe { &CLASS_NAME };
 
-                let class: &'static objc_util::runtime::Class = unsafe {
-                    core::mem::transmute(
-                        objc_opt_class(core::ptr::read_volatile(&CLASS_REF) as *const _ as *const _)
+                let class_ref = unsafe { core::ptr::read_volatile(&CLASS_REF) } as *const u8;
+                #[cfg(#opt_class_cfg)]
+                let class = {
+                    #[link(name = "objc", kind = "dylib")]
+                    extern {
+                        fn objc_opt_class(name: *const std::os::raw::c_void) -> *mut std::os::raw::c_void;
+                    }
+                    unsafe { objc_opt_class(class_ref as *const _) }
+                };
+                #[cfg(not(#opt_class_cfg))]
+                let class = unsafe {
+                    objc_util::dispatch::send::<_, *mut std::os::raw::c_void>(
+                        class_ref as *mut objc_util::runtime::Object,
+                        objc_util::objc::sel_impl!("class\0"),
+                        (),
                     )
                 };
+                let class: &'static objc_util::runtime::Class = unsafe { core::mem::transmute(class) };
                 class
             }
         },
         class.span(),
-    );
-    parsed.into()
-}
-
-#[cfg(not(feature = "compile-time"))]
-#[proc_macro]
-pub fn class_impl(input: TokenStream) -> TokenStream {
-    let input: proc_macro2::TokenStream = parse_macro_input!(input);
-    let tokens = quote::quote! {
-        fn proc_macro_support_wrapper() -> &'static objc_util::runtime::Class {
-            objc_util::objc::class!(#input)
-        }
-    };
-    tokens.into()
+    )
 }
 
+/// Declares bindings to the Objective-C methods in a block of foreign items.
+///
+/// A binding marked `deprecated` for an OS gets a `#[deprecated]` attribute
+/// when that OS's deployment target (`MACOSX_DEPLOYMENT_TARGET`,
+/// `IPHONEOS_DEPLOYMENT_TARGET`, ...) is at or past the version. Cargo
+/// doesn't know the expansion depends on those variables, so a crate using
+/// these keys should print `cargo:rerun-if-env-changed` for them from its
+/// `build.rs`, or changing the deployment target won't recompile it.
+///
+/// The bindings dispatch through `objc_util::mock` when the crate declaring
+/// them has its own `mock` feature enabled. Crates with bindings should
+/// declare one, forwarded as `mock = ["objc-util/mock"]`, or rustc warns about
+/// the unexpected `feature = "mock"` cfg.
 #[proc_macro_attribute]
 pub fn extern_objc(args: TokenStream, input: TokenStream) -> TokenStream {
     let framework: Framework = parse_macro_input!(args);
@@ -163,3 +451,107 @@ pub fn extern_objc(args: TokenStream, input: TokenStream) -> TokenStream {
     };
     parsed.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `cfg` holds on a target with the `target` cfg values.
+    fn cfg_holds(cfg: &syn::NestedMeta, target: &[(&str, &str)]) -> bool {
+        match cfg {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => match &nv.lit {
+                syn::Lit::Str(value) => target.iter().any(|&(name, target_value)| {
+                    nv.path.is_ident(name) && value.value() == target_value
+                }),
+                _ => panic!("unexpected cfg value"),
+            },
+            syn::NestedMeta::Meta(syn::Meta::List(list)) => {
+                let mut nested = list.nested.iter().map(|cfg| cfg_holds(cfg, target));
+                match list.path.get_ident().unwrap().to_string().as_str() {
+                    "any" => nested.any(|holds| holds),
+                    "all" => nested.all(|holds| holds),
+                    "not" => !nested.next().unwrap(),
+                    name => panic!("unexpected cfg `{}`", name),
+                }
+            }
+            _ => panic!("unexpected cfg"),
+        }
+    }
+
+    /// The initializer of the `class` the static class reference of `NSData`
+    /// resolves through on `target`, given the `targets` environment.
+    fn class_lookup(
+        class_ref: ClassRef,
+        targets: &[(&str, &str)],
+        target: &[(&str, &str)],
+    ) -> String {
+        let class = syn::Ident::new("NSData", proc_macro2::Span::call_site());
+        let body = static_class_body(&class, class_ref, |var| {
+            targets
+                .iter()
+                .find(|&&(name, _)| name == var)
+                .map(|&(_, deployment_target)| deployment_target.to_string())
+        });
+        let block: syn::Block = syn::parse2(body).unwrap();
+        let lookups = block.stmts.iter().filter_map(|stmt| match stmt {
+            syn::Stmt::Local(local) if !local.attrs.is_empty() => {
+                let cfg = match local.attrs[0].parse_meta().unwrap() {
+                    syn::Meta::List(list) if list.path.is_ident("cfg") => list.nested[0].clone(),
+                    _ => panic!("expected a cfg"),
+                };
+                let (_, init) = local.init.as_ref().unwrap();
+                Some((cfg, quote::quote!(#init).to_string()))
+            }
+            _ => None,
+        });
+        let mut active = lookups.filter(|(cfg, _)| cfg_holds(cfg, target));
+        let (_, init) = active.next().expect("no lookup for the target");
+        assert!(active.next().is_none(), "several lookups for the target");
+        if init.contains("objc_opt_class") {
+            "objc_opt_class".to_string()
+        } else {
+            assert!(init.contains(r#"sel_impl ! ("class\0")"#), "{}", init);
+            "+class".to_string()
+        }
+    }
+
+    #[test]
+    fn objc_opt_class_fallback() {
+        let macos = [("target_os", "macos")];
+        let ios = [("target_os", "ios")];
+        let catalyst = [("target_os", "ios"), ("target_abi", "macabi")];
+        let lookups: &[(&[(&str, &str)], &[(&str, &str)], &str)] = &[
+            (&[], &macos, "+class"),
+            (&[], &ios, "+class"),
+            (&[("MACOSX_DEPLOYMENT_TARGET", "10.14")], &macos, "+class"),
+            (
+                &[("MACOSX_DEPLOYMENT_TARGET", "10.15")],
+                &macos,
+                "objc_opt_class",
+            ),
+            (&[("MACOSX_DEPLOYMENT_TARGET", "10.15")], &ios, "+class"),
+            (&[("IPHONEOS_DEPLOYMENT_TARGET", "12.4")], &ios, "+class"),
+            (
+                &[("IPHONEOS_DEPLOYMENT_TARGET", "13.0")],
+                &ios,
+                "objc_opt_class",
+            ),
+            (
+                &[("IPHONEOS_DEPLOYMENT_TARGET", "13.0")],
+                &catalyst,
+                "objc_opt_class",
+            ),
+            (&[("IPHONEOS_DEPLOYMENT_TARGET", "13.0")], &macos, "+class"),
+        ];
+        for &(targets, target, expected) in lookups {
+            assert_eq!(
+                class_lookup(ClassRef::Class, targets, target),
+                expected,
+                "{:?} on {:?}",
+                targets,
+                target
+            );
+            assert_eq!(class_lookup(ClassRef::Super, targets, target), expected);
+        }
+    }
+}
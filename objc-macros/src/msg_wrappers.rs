@@ -1,12 +1,19 @@
-use crate::objc_attr::ObjCAttr;
+use crate::objc_attr::{Nullability, ObjCAttr, Superclass};
 use quote::ToTokens;
 use std::convert::{TryFrom, TryInto};
 use syn::{parse, spanned::Spanned};
 
 pub struct MsgWrappers {
+    impl_block: Option<ImplBlock>,
     funcs: Vec<MsgWrapper>,
 }
 
+/// The `impl` block method-style bindings were declared in.
+struct ImplBlock {
+    attrs: Vec<syn::Attribute>,
+    self_ty: syn::Type,
+}
+
 impl TryFrom<Vec<syn::ForeignItem>> for MsgWrappers {
     type Error = syn::Error;
 
@@ -14,22 +21,181 @@ impl TryFrom<Vec<syn::ForeignItem>> for MsgWrappers {
         let funcs = items
             .into_iter()
             .map(|item| match item {
-                syn::ForeignItem::Fn(f) => f.try_into(),
+                syn::ForeignItem::Fn(f) => accessors(f, None),
+                item => Err(syn::Error::new(
+                    item.span(),
+                    "Expected a function declaration",
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Ok(Self {
+            impl_block: None,
+            funcs,
+        })
+    }
+}
+
+impl TryFrom<syn::ItemImpl> for MsgWrappers {
+    type Error = syn::Error;
+
+    fn try_from(item: syn::ItemImpl) -> parse::Result<Self> {
+        let syn::ItemImpl {
+            attrs,
+            defaultness,
+            unsafety,
+            impl_token: _,
+            generics,
+            trait_,
+            self_ty,
+            brace_token: _,
+            items,
+        } = item;
+
+        match defaultness {
+            Some(a) => {
+                return Err(syn::Error::new(
+                    a.span(),
+                    "ObjC bindings cannot be `default`",
+                ))
+            }
+            None => {}
+        }
+
+        match unsafety {
+            Some(a) => {
+                return Err(syn::Error::new(
+                    a.span(),
+                    "ObjC bindings are implicitly `unsafe`",
+                ))
+            }
+            None => {}
+        }
+
+        match generics.lt_token {
+            Some(_) => {
+                return Err(syn::Error::new(
+                    generics.span(),
+                    "ObjC bindings with generics are unsupported",
+                ))
+            }
+            None => {}
+        }
+
+        match trait_ {
+            Some((_, path, _)) => {
+                return Err(syn::Error::new(
+                    path.span(),
+                    "ObjC bindings must be declared in an inherent `impl` block",
+                ))
+            }
+            None => {}
+        }
+
+        match &*self_ty {
+            syn::Type::Path(syn::TypePath { qself: None, .. }) => {}
+            other => return Err(syn::Error::new(other.span(), "Expected a type name")),
+        }
+
+        let funcs = items
+            .into_iter()
+            .map(|item| match item {
+                syn::ImplItem::Method(m) => accessors(declaration(m)?, Some(&*self_ty)),
                 item => Err(syn::Error::new(
                     item.span(),
                     "Expected a function declaration",
                 )),
             })
-            .collect::<Result<_, _>>()?;
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
 
-        Ok(Self { funcs })
+        Ok(Self {
+            impl_block: Some(ImplBlock {
+                attrs,
+                self_ty: *self_ty,
+            }),
+            funcs,
+        })
+    }
+}
+
+/// The wrapper for `item`, followed by the `set_` wrapper of a writable
+/// `property`.
+fn accessors(
+    item: syn::ForeignItemFn,
+    self_ty: Option<&syn::Type>,
+) -> parse::Result<Vec<MsgWrapper>> {
+    let setter_item = item.clone();
+    let getter: MsgWrapper = (item, self_ty).try_into()?;
+    let setter = match getter.objc_attr.setter_attr() {
+        Some(objc_attr) => Some(MsgWrapper::new(
+            getter.setter_decl(setter_item),
+            self_ty,
+            objc_attr,
+        )?),
+        None => None,
+    };
+    Ok(std::iter::once(getter).chain(setter).collect())
+}
+
+/// syn accepts methods without a body in `impl` blocks, standing in a lone `;`
+/// item for the body.
+fn declaration(method: syn::ImplItemMethod) -> parse::Result<syn::ForeignItemFn> {
+    let syn::ImplItemMethod {
+        attrs,
+        vis,
+        defaultness,
+        sig,
+        block,
+    } = method;
+    match defaultness {
+        Some(a) => {
+            return Err(syn::Error::new(
+                a.span(),
+                "ObjC bindings cannot be `default`",
+            ))
+        }
+        None => {}
+    }
+    match &block.stmts[..] {
+        [syn::Stmt::Item(syn::Item::Verbatim(semi))] if semi.to_string() == ";" => {
+            Ok(syn::ForeignItemFn {
+                attrs,
+                vis,
+                sig,
+                semi_token: syn::token::Semi {
+                    spans: [block.brace_token.span],
+                },
+            })
+        }
+        _ => Err(syn::Error::new(
+            block.span(),
+            "ObjC bindings cannot have a body",
+        )),
     }
 }
 
 impl ToTokens for MsgWrappers {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let mut items = proc_macro2::TokenStream::new();
         for func in &self.funcs {
-            func.to_tokens(tokens)
+            func.to_tokens(&mut items);
+            // Traits can't be declared inside an `impl` block.
+            func.unavailable_traits(tokens);
+        }
+        match &self.impl_block {
+            Some(ImplBlock { attrs, self_ty }) => tokens.extend(quote::quote! {
+                #(#attrs)*
+                impl #self_ty {
+                    #items
+                }
+            }),
+            None => tokens.extend(items),
         }
     }
 }
@@ -40,16 +206,66 @@ struct MsgWrapper {
     vis: syn::Visibility,
     fn_token: syn::token::Fn,
     ident: syn::Ident,
+    /// The type of the enclosing `impl` block, if any.
+    self_ty: Option<syn::Type>,
     receiver: MsgReceiver,
-    args: Vec<(Vec<syn::Attribute>, syn::PatIdent, syn::Type)>,
+    /// The superclass argument of a `super` binding.
+    superclass_arg: Option<MsgArg>,
+    args: Vec<MsgArg>,
+    /// The trailing `...`, taken by the wrapper as a `varargs` tuple.
+    variadic: Option<syn::Variadic>,
     output: syn::ReturnType,
+    /// What the message returns: `output`, or `T` for a `catch_exceptions`
+    /// binding returning `Result<T, Id<NSException>>`.
+    message_output: syn::ReturnType,
+    output_kind: OutputKind,
     span: proc_macro2::Span,
 }
 
-impl TryFrom<syn::ForeignItemFn> for MsgWrapper {
+impl TryFrom<(syn::ForeignItemFn, Option<&syn::Type>)> for MsgWrapper {
     type Error = syn::Error;
 
-    fn try_from(item: syn::ForeignItemFn) -> parse::Result<Self> {
+    fn try_from(
+        (mut item, self_ty): (syn::ForeignItemFn, Option<&syn::Type>),
+    ) -> parse::Result<Self> {
+        let objc_attr = {
+            let sig = &item.sig;
+            let attrs = &mut item.attrs;
+            let objc_attrs: Vec<ObjCAttr> = (0..attrs.len())
+                .rev()
+                .filter_map(|idx| {
+                    if ObjCAttr::is_objc(&attrs[idx]) {
+                        Some((attrs.remove(idx), sig).try_into())
+                    } else {
+                        None
+                    }
+                })
+                .collect::<parse::Result<_>>()?;
+            if objc_attrs.len() == 0 {
+                return Err(syn::Error::new(
+                    item.sig.ident.span(),
+                    "Missing `#[objc(selector = \"xx:xx:xx:\", version = \"#-#-#\")]` attribute",
+                ));
+            }
+
+            if objc_attrs.len() > 1 {
+                return Err(syn::Error::new(
+                    objc_attrs[1].span(),
+                    "Duplicate `objc` attributes",
+                ));
+            }
+            objc_attrs.into_iter().next().unwrap()
+        };
+        Self::new(item, self_ty, objc_attr)
+    }
+}
+
+impl MsgWrapper {
+    fn new(
+        item: syn::ForeignItemFn,
+        self_ty: Option<&syn::Type>,
+        objc_attr: ObjCAttr,
+    ) -> parse::Result<Self> {
         let span = item.span();
         let syn::ForeignItemFn {
             mut attrs,
@@ -111,16 +327,6 @@ impl TryFrom<syn::ForeignItemFn> for MsgWrapper {
             None => {}
         }
 
-        match variadic {
-            Some(a) => {
-                return Err(syn::Error::new(
-                    a.span(),
-                    "ObjC bindings with variadic arguments are unsupported",
-                ))
-            }
-            None => {}
-        }
-
         match generics.lt_token {
             Some(_) => {
                 return Err(syn::Error::new(
@@ -131,54 +337,99 @@ impl TryFrom<syn::ForeignItemFn> for MsgWrapper {
             None => {}
         }
 
-        let objc_attr = {
-            let objc_attrs: Vec<ObjCAttr> = (0..attrs.len())
-                .rev()
-                .filter_map(|idx| {
-                    if ObjCAttr::is_objc(&attrs[idx]) {
-                        Some(attrs.remove(idx).try_into())
-                    } else {
-                        None
-                    }
-                })
-                .collect::<parse::Result<_>>()?;
-            if objc_attrs.len() == 0 {
-                return Err(syn::Error::new(
-                    ident.span(),
-                    "Missing `#[objc(selector = \"xx:xx:xx:\", version = \"#-#-#\")]` attribute",
-                ));
-            }
-
-            if objc_attrs.len() > 1 {
-                return Err(syn::Error::new(
-                    objc_attrs[1].span(),
-                    "Duplicate `objc` attributes",
-                ));
-            }
-            objc_attrs.into_iter().next().unwrap()
-        };
-
         let sel_arg_count = objc_attr.objc_meth_name.arg_count();
-        let rust_arg_count = inputs.len();
-        if sel_arg_count != rust_arg_count {
+        // `super` bindings take the superclass after the receiver.
+        let superclass_arg_count =
+            matches!(objc_attr.superclass, Some(Superclass::Arg)) as usize;
+        let rust_arg_count = (inputs.len() + objc_attr.class.is_some() as usize)
+            .saturating_sub(superclass_arg_count);
+        // `throws` bindings pass the trailing `error:` argument themselves.
+        let hidden_arg_count = objc_attr.throws.is_some() as usize;
+        if sel_arg_count != rust_arg_count + hidden_arg_count {
             return Err(syn::Error::new(
                 objc_attr.objc_meth_name.span(),
                 format!(
-                    "ObjC selector has `{}` argument{}, but the rust binding has `{}` argument{}",
+                    "ObjC selector has `{}` argument{}, but the rust binding has `{}` argument{}{}{}",
                     sel_arg_count,
                     if sel_arg_count == 1 { "" } else { "s" },
                     rust_arg_count,
                     if rust_arg_count == 1 { "" } else { "s" },
+                    if hidden_arg_count == 1 {
+                        " plus the hidden `error:` argument"
+                    } else {
+                        ""
+                    },
+                    if objc_attr.derived_selector {
+                        format!(
+                            ". `{}` was derived from the name: separate keywords with `__` or add a `selector` key",
+                            objc_attr.objc_meth_name.as_string(),
+                        )
+                    } else {
+                        String::new()
+                    },
                 ),
             ));
         }
+        if let (Some(span), Some(_)) = (objc_attr.throws, &variadic) {
+            return Err(syn::Error::new(span, "`throws` bindings can't be variadic"));
+        }
+        if let (Some(_), Some(variadic)) = (&objc_attr.superclass, &variadic) {
+            return Err(syn::Error::new(
+                variadic.span(),
+                "Messages to `super` can't be variadic",
+            ));
+        }
 
-        let mut args = inputs
-            .into_iter()
+        // The generated wrappers declare nested items, where `Self` isn't in scope.
+        let resolve_self = |ty: syn::Type| match self_ty {
+            Some(self_ty) => syn::parse2(replace_self(ty.into_token_stream(), self_ty)),
+            None => Ok(ty),
+        };
+        let output = match output {
+            syn::ReturnType::Type(arrow, ty) => {
+                syn::ReturnType::Type(arrow, Box::new(resolve_self(*ty)?))
+            }
+            o => o,
+        };
+        let message_output = match objc_attr.catch_exceptions {
+            Some(span) => caught_output(&output, span)?,
+            None => output.clone(),
+        };
+        let output_kind = match objc_attr.throws {
+            Some(span) => OutputKind::throws(&message_output, span, objc_attr.nullability)?,
+            None => OutputKind::new(&message_output, objc_attr.nullability)?,
+        };
+        match (objc_attr.ownership, output_kind.success()) {
+            (None, _) | (_, OutputKind::Id(_)) | (_, OutputKind::OptionId(_)) => {}
+            (Some((_, span)), _) => {
+                return Err(syn::Error::new(
+                    span,
+                    "Ownership only applies to `Id` return types",
+                ))
+            }
+        }
+        let mut inputs = inputs.into_iter();
+        let receiver: MsgReceiver = match &objc_attr.class {
+            Some(class) => MsgReceiver::class(class.clone(), objc_attr.lookup),
+            None => (inputs.next().unwrap(), self_ty).try_into()?,
+        };
+        if let ReceiverKind::Owned(ty) = &receiver.kind {
+            if !objc_attr.objc_meth_name.family().consumes_receiver() {
+                return Err(syn::Error::new(
+                    ty.span(),
+                    "Only `init` methods consume their receiver. Take a raw pointer instead.",
+                ));
+            }
+        }
+        let args = inputs
             .map(|input| match input {
+                syn::FnArg::Receiver(r) if objc_attr.class.is_some() => Err(syn::Error::new(
+                    r.span(),
+                    "ObjC bindings with a `class` take no receiver",
+                )),
                 syn::FnArg::Receiver(r) => Err(syn::Error::new(
                     r.span(),
-                    "ObjC bindings with `self` is unsupported",
+                    "`self` must be the first argument",
                 )),
                 syn::FnArg::Typed(syn::PatType {
                     attrs,
@@ -195,11 +446,21 @@ impl TryFrom<syn::ForeignItemFn> for MsgWrapper {
                             ))
                         }
                     };
-                    Ok((attrs, pat_ident, *ty))
+                    MsgArg::new(attrs, pat_ident, resolve_self(*ty)?)
                 }
             })
             .collect::<parse::Result<Vec<_>>>()?;
-        let receiver = args.remove(0).try_into()?;
+        let (superclass_arg, args) = match objc_attr.superclass {
+            Some(Superclass::Arg) => {
+                let mut args = args.into_iter();
+                (args.next(), args.collect())
+            }
+            _ => (None, args),
+        };
+        // `__` separates the keywords of derived selectors.
+        if objc_attr.derived_selector && ident.to_string().contains("__") {
+            attrs.push(syn::parse_quote!(#[allow(non_snake_case)]));
+        }
 
         Ok(Self {
             attrs,
@@ -207,96 +468,527 @@ impl TryFrom<syn::ForeignItemFn> for MsgWrapper {
             vis,
             fn_token,
             ident,
+            self_ty: self_ty.cloned(),
             receiver,
+            superclass_arg,
             args,
+            variadic,
             output,
+            message_output,
+            output_kind,
             span,
         })
     }
 }
 
 impl MsgWrapper {
-    fn func_impl(&self, tokens: &mut proc_macro2::TokenStream) {
+    /// The wrapper itself, sending through the mock runtime if `mock` is set.
+    /// Both are emitted, each behind the consuming crate's `mock` feature.
+    fn func_impl(&self, tokens: &mut proc_macro2::TokenStream, mock: bool) {
         let Self {
             attrs,
             objc_attr: _,
             vis,
             fn_token,
             ident,
+            self_ty: _,
             receiver,
+            superclass_arg: _,
             args,
+            variadic,
             output,
+            message_output,
+            output_kind: _,
             span,
         } = self;
+        // The mock runtime catches exceptions without the trampoline.
+        if let (Some(span), false) = (
+            self.objc_attr.catch_exceptions,
+            mock || cfg!(feature = "exception"),
+        ) {
+            tokens.extend(quote::quote_spanned! {span=>
+                #[cfg(not(feature = "mock"))]
+                compile_error!("`catch_exceptions` requires the `exception` feature");
+            });
+            return;
+        }
         let void_ptr = syn::Type::Ptr(syn::parse_str("*const std::os::raw::c_void").unwrap());
-        let sel_func_ident = self.sel_func_ident();
+        let bool_type: syn::Type = syn::parse_quote!(objc_util::runtime::BOOL);
+        let sel_func_path = self.assoc_path(&self.sel_func_ident());
         let receiver_target_type = receiver.target_type();
-        let receiver_name = &receiver.name;
-        let message_names = args.iter().map(|(_, ident, _)| ident);
-        let message_types = args.iter().map(|(_, _, ty)| match ty {
-            // syn::Type::Ptr(p) => &p.elem,
-            o => o,
-        });
-        let middle_message_types = args
+        let receiver_type = &receiver.type_;
+        let receiver_value = receiver.value();
+        let message_names = args.iter().map(|arg| &arg.name.ident);
+        let encode_bounds = args.iter().map(|arg| arg.encode_bound(mock));
+        let mut middle_message_types = args
             .iter()
-            .map(|(_, _, ty)| match ty {
-                // syn::Type::Ptr(_) => &void_ptr,
-                o => o,
-            })
+            .map(|arg| arg.message_type(mock))
             .collect::<Vec<_>>();
-        let all_args_iter = std::iter::once(receiver.as_tuple())
-            .chain(args.iter().map(|(attrs, ident, ty)| (attrs, ident, ty)));
-        let func_args = all_args_iter
-            .clone()
-            .map(|(attrs, ident, ty)| {
-                quote::quote! {
-                    #(#attrs)* #ident: #ty,
-                }
-            })
+        let mut message_values = message_names
+            .zip(&middle_message_types)
+            .map(|(name, ty)| quote::quote!(#name as #ty))
+            .collect::<Vec<_>>();
+        let lower_stmts = args.iter().map(|arg| arg.lower_stmt(mock));
+        let func_args = self.func_args();
+        let mut decl_args = std::iter::once(receiver.decl_arg())
+            .chain(self.superclass_arg.iter().map(|arg| arg.decl_arg(mock)))
+            .chain(args.iter().map(|arg| arg.decl_arg(mock)))
             .collect::<proc_macro2::TokenStream>();
+        let mut decl_output = message_output.to_token_stream();
+        // `throws` bindings pass a pointer to `__error` as the last argument.
+        let error_stmt = match &self.output_kind {
+            OutputKind::Throws(_, error) => {
+                let error_target = match error {
+                    syn::Type::Ptr(ptr) => &*ptr.elem,
+                    _ => unreachable!(),
+                };
+                if mock {
+                    middle_message_types.push(syn::parse_quote!(*mut #error));
+                    message_values.push(quote::quote!(&mut __error as *mut #error));
+                } else {
+                    middle_message_types
+                        .push(syn::parse_quote!(objc_util::ErrorOut<#error_target>));
+                    message_values.push(quote::quote!(objc_util::ErrorOut(&mut __error)));
+                }
+                decl_args.extend(quote::quote!(__error: *mut #error,));
+                Some(quote::quote! {
+                    let mut __error: #error = std::ptr::null_mut();
+                })
+            }
+            _ => None,
+        };
         let unit = syn::Type::Tuple(syn::TypeTuple {
             paren_token: Default::default(),
             elems: Default::default(),
         });
-        let output_type = match output {
+        let output_type = match message_output {
             syn::ReturnType::Default => &unit,
             syn::ReturnType::Type(_, t) => &*t,
         };
-        let middle_type = match output_type {
-            syn::Type::Ptr(_) => &void_ptr,
+        let object_ptr: syn::Type = syn::parse_quote!(*mut objc_util::runtime::Object);
+        let verify_type = match self.output_kind.success() {
+            OutputKind::Bool => &bool_type,
+            OutputKind::Id(_) | OutputKind::OptionId(_) => &object_ptr,
+            OutputKind::NonNull(ptr) | OutputKind::OptionNonNull(ptr) => ptr,
+            _ => output_type,
+        };
+        if let OutputKind::Throws(..) = &self.output_kind {
+            decl_output = quote::quote!(-> #verify_type);
+        }
+        // The mock runtime matches implementations on the exact declared types.
+        let middle_type = match verify_type {
+            syn::Type::Ptr(_) if !mock => &void_ptr,
             o => o,
         };
+        let nil_message = format!("`{}` returned nil", self.qualified_name());
+        let from_ptr = if self.objc_attr.returns_retained() {
+            quote::quote!(objc_util::Id::from_retained_ptr)
+        } else {
+            quote::quote!(objc_util::Id::from_autoreleased_ptr)
+        };
+        let lift_result = match &self.output_kind {
+            OutputKind::Value => quote::quote!(result as #output_type),
+            OutputKind::Nonnull => quote::quote! {
+                let result = result as #output_type;
+                debug_assert!(!result.is_null(), #nil_message);
+                result
+            },
+            OutputKind::Bool => quote::quote!(result != objc_util::runtime::NO),
+            OutputKind::NonNull(ptr) => quote::quote! {
+                std::ptr::NonNull::new(result as #ptr).expect(#nil_message)
+            },
+            OutputKind::OptionNonNull(ptr) => quote::quote! {
+                std::ptr::NonNull::new(result as #ptr)
+            },
+            OutputKind::Id(ptr) => quote::quote! {
+                match #from_ptr(result as #ptr) {
+                    Some(id) => id,
+                    None => panic!(#nil_message),
+                }
+            },
+            OutputKind::OptionId(ptr) => quote::quote!(#from_ptr(result as #ptr)),
+            OutputKind::Throws(success, _) => {
+                let success = match &**success {
+                    OutputKind::Bool => quote::quote! {
+                        if result != objc_util::runtime::NO {
+                            Some(())
+                        } else {
+                            None
+                        }
+                    },
+                    OutputKind::Id(ptr) => quote::quote!(#from_ptr(result as #ptr)),
+                    OutputKind::NonNull(ptr) => {
+                        quote::quote!(std::ptr::NonNull::new(result as #ptr))
+                    }
+                    _ => unreachable!(),
+                };
+                let no_error_message =
+                    format!("`{}` failed without an error", self.qualified_name());
+                // The error is autoreleased, but not a return value.
+                quote::quote! {
+                    match #success {
+                        Some(result) => Ok(result),
+                        None => match objc_util::Id::from_ptr(__error) {
+                            Some(error) => Err(error),
+                            None => panic!(#no_error_message),
+                        },
+                    }
+                }
+            }
+        };
+        let (verify_message, verify_receiver) = if mock {
+            (
+                quote::quote!(objc_util::mock::verify_message),
+                quote::quote!(__receiver),
+            )
+        } else {
+            (
+                quote::quote!(objc_util::Message::verify_message),
+                quote::quote!(&*__receiver),
+            )
+        };
+        let superclass_stmt = match (&self.objc_attr.superclass, &self.superclass_arg) {
+            (Some(Superclass::Named(class)), _) => {
+                let class = crate::superclass_func_body(class, self.objc_attr.lookup);
+                Some(quote::quote! {
+                    let __superclass: *const objc_util::runtime::Class = #class;
+                })
+            }
+            (_, Some(arg)) => {
+                let name = &arg.name.ident;
+                Some(quote::quote! {
+                    let __superclass = #name as *const objc_util::runtime::Class;
+                })
+            }
+            _ => None,
+        };
+        let send_stmts = if mock {
+            // The mock runtime takes the variadic arguments as a trailing tuple.
+            let varargs = variadic.as_ref().map(|_| quote::quote!(varargs,));
+            let send = match superclass_stmt {
+                Some(_) => quote::quote! {
+                    objc_util::mock::send_super_message(__receiver, __superclass, sel, (#(#message_values,)*))
+                },
+                None => quote::quote! {
+                    objc_util::mock::send_message(__receiver, sel, (#(#message_values,)* #varargs))
+                },
+            };
+            quote::quote! {
+                let result: #middle_type = match #send {
+                    Ok(o) => o,
+                    Err(e) => panic!("{}", e),
+                };
+                #lift_result
+            }
+        } else {
+            // All pick the `objc_msgSend` variant for the return type. Caught
+            // exceptions have to unwind out of the send, which only the
+            // `_unwind` variants allow.
+            let (send, send_super, send_varargs) = match self.objc_attr.catch_exceptions {
+                Some(_) => (
+                    quote::quote!(objc_util::dispatch::send_unwind),
+                    quote::quote!(objc_util::dispatch::send_super_unwind),
+                    quote::quote!(objc_util::varargs::send_unwind),
+                ),
+                None => (
+                    quote::quote!(objc_util::dispatch::send),
+                    quote::quote!(objc_util::dispatch::send_super),
+                    quote::quote!(objc_util::varargs::send),
+                ),
+            };
+            let send = match variadic {
+                None if superclass_stmt.is_some() => quote::quote! {
+                    #send_super::<_, #middle_type>(
+                        __receiver as *mut objc_util::runtime::Object,
+                        __superclass,
+                        sel,
+                        (#(#message_values,)*),
+                    )
+                },
+                Some(_) => quote::quote! {
+                    #send_varargs::<_, _, #middle_type>(
+                        __receiver as *mut objc_util::runtime::Object,
+                        sel,
+                        (#(#message_values,)*),
+                        varargs,
+                    )
+                },
+                None => quote::quote! {
+                    #send::<_, #middle_type>(
+                        __receiver as *mut objc_util::runtime::Object,
+                        sel,
+                        (#(#message_values,)*),
+                    )
+                },
+            };
+            quote::quote! {
+                let result: #middle_type = #send;
+                #lift_result
+            }
+        };
+        // Exceptions unwind no further than the wrapper.
+        let send_stmts = match self.objc_attr.catch_exceptions {
+            Some(_) => quote::quote! {
+                objc_util::exception::catch(|| { #send_stmts })
+            },
+            None => send_stmts,
+        };
+        // Method type encodings only describe the fixed arguments.
+        let verify_message_types = match variadic {
+            Some(_) if mock => quote::quote!(#(#middle_message_types,)* __V,),
+            _ => quote::quote!(#(#middle_message_types,)*),
+        };
+        let generics = self.generics();
         let cfgs = self.objc_attr.versions.os_cfgs();
+        let deprecated_attrs = self
+            .objc_attr
+            .versions
+            .deprecated_attrs(|var| std::env::var(var).ok());
         let debug_assert_stmt = self.objc_attr.versions.debug_assert_stmt(ident);
+        let mock_cfg = if mock {
+            quote::quote!(feature = "mock")
+        } else {
+            quote::quote!(not(feature = "mock"))
+        };
         let stream = quote::quote! {
             #(#attrs)*
+            #[cfg(#mock_cfg)]
             #cfgs
-            #vis unsafe #fn_token #ident(#func_args) #output
+            #deprecated_attrs
+            #vis unsafe #fn_token #ident #generics(#func_args) #output
             where
                 #receiver_target_type: objc_util::Message,
                 #middle_type: objc_util::Encode,
-                #(#message_types: objc_util::Encode,)*
+                #(#encode_bounds)*
             {
                 #[deny(improper_ctypes)]
                 #[allow(unused)]
-                extern "C" { fn #ident(#func_args) #output; }
+                extern "C" { fn #ident(#decl_args) #decl_output; }
 
                 #debug_assert_stmt
 
-                let sel = #sel_func_ident();
+                let sel = #sel_func_path();
+                let __receiver: #receiver_type = #receiver_value;
+                #superclass_stmt
+                #(#lower_stmts)*
+                #error_stmt
 
                 if cfg!(debug_assertions) {
-                    match objc_util::Message::verify_message::<(#(#middle_message_types,)*), #output_type>(&*#receiver_name, sel) {
+                    match #verify_message::<(#verify_message_types), #verify_type>(#verify_receiver, sel) {
                         Ok(()) => {}
                         Err(e) => panic!("Binding error on `{}`: {}", stringify!(#ident), e),
                     }
                 }
 
-                let result: #middle_type = match objc_util::Message::send_message(&*#receiver_name, sel, (#(#message_names as #middle_message_types,)*)) {
-                    Ok(o) => o,
-                    Err(e) => panic!("{}", e),
-                };
-                result as #output_type
+                #send_stmts
+            }
+        };
+        tokens.extend(crate::assign_group_to_span(stream, *span))
+    }
+
+    /// Turns the getter declaration of a property into its setter's:
+    /// `set_frame`, taking the getter's inputs and a `value`. Objects are set
+    /// through raw pointers.
+    fn setter_decl(&self, mut item: syn::ForeignItemFn) -> syn::ForeignItemFn {
+        item.attrs.retain(|attr| !ObjCAttr::is_objc(attr));
+        let ident = &item.sig.ident;
+        item.sig.ident = syn::Ident::new(&format!("set_{}", ident), ident.span());
+        let value_type = match &self.output_kind {
+            OutputKind::NonNull(ptr)
+            | OutputKind::OptionNonNull(ptr)
+            | OutputKind::Id(ptr)
+            | OutputKind::OptionId(ptr) => ptr.clone(),
+            _ => match &self.message_output {
+                syn::ReturnType::Type(_, ty) => (**ty).clone(),
+                syn::ReturnType::Default => syn::parse_quote!(()),
+            },
+        };
+        item.sig.inputs.push(syn::parse_quote!(value: #value_type));
+        item.sig.output = syn::ReturnType::Default;
+        item
+    }
+
+    /// The wrapper's arguments, as declared.
+    fn func_args(&self) -> proc_macro2::TokenStream {
+        let varargs = self.variadic.as_ref().map(|variadic| {
+            let attrs = &variadic.attrs;
+            quote::quote! {
+                #(#attrs)* varargs: __V,
+            }
+        });
+        std::iter::once(self.receiver.func_arg())
+            .chain(self.superclass_arg.iter().map(MsgArg::func_arg))
+            .chain(self.args.iter().map(MsgArg::func_arg))
+            .chain(varargs)
+            .collect()
+    }
+
+    fn generics(&self) -> Option<proc_macro2::TokenStream> {
+        self.variadic.as_ref().map(|_| {
+            quote::quote! {
+                <__V: objc_util::VarArgs>
+            }
+        })
+    }
+
+    /// `ident` as called from another wrapper of the same binding.
+    fn assoc_path(&self, ident: &syn::Ident) -> proc_macro2::TokenStream {
+        match self.self_ty {
+            Some(_) => quote::quote!(Self::#ident),
+            None => quote::quote!(#ident),
+        }
+    }
+
+    /// The binding's name in diagnostics: `hash` or `NSObject::hash`.
+    fn qualified_name(&self) -> String {
+        match &self.self_ty {
+            Some(syn::Type::Path(path)) => {
+                format!(
+                    "{}::{}",
+                    path.path.segments.last().unwrap().ident,
+                    self.ident
+                )
+            }
+            _ => self.ident.to_string(),
+        }
+    }
+
+    fn unavailable_trait_ident(&self) -> syn::Ident {
+        let name = self.qualified_name().replace("::", "_");
+        syn::Ident::new(&format!("_unavailable_{}", name), self.ident.span())
+    }
+
+    /// The traits bounding the stand-ins emitted by `unavailable_impl`. Nothing
+    /// implements them, so their `on_unimplemented` message is what users see.
+    /// The `diagnostic` namespace is why objc-util needs Rust 1.78.
+    fn unavailable_traits(&self, tokens: &mut proc_macro2::TokenStream) {
+        let trait_ident = self.unavailable_trait_ident();
+        let vis = &self.vis;
+        for (cfg, os) in self.objc_attr.versions.unavailable() {
+            let message = format!("`{}` is unavailable on {}", self.qualified_name(), os);
+            let label = format!("unavailable on {}", os);
+            let stream = quote::quote! {
+                #[cfg(#cfg)]
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                #[diagnostic::on_unimplemented(message = #message, label = #label)]
+                #vis trait #trait_ident {}
+            };
+            tokens.extend(crate::assign_group_to_span(stream, self.span))
+        }
+    }
+
+    /// On platforms the binding is marked unavailable on, a stand-in whose
+    /// receiver bound can never be met, so calls fail to compile with a
+    /// diagnostic naming the platform instead of "cannot find function".
+    fn unavailable_impl(&self, tokens: &mut proc_macro2::TokenStream) {
+        let Self {
+            attrs,
+            objc_attr,
+            vis,
+            fn_token,
+            ident,
+            self_ty: _,
+            receiver,
+            superclass_arg: _,
+            args: _,
+            variadic: _,
+            output,
+            message_output: _,
+            output_kind: _,
+            span,
+        } = self;
+        let trait_ident = self.unavailable_trait_ident();
+        let receiver_target_type = receiver.target_type();
+        let func_args = self.func_args();
+        let generics = self.generics();
+        let try_func_ident = self.try_func_ident();
+        let try_output = self.try_output();
+        let funcs = [
+            (ident, output.to_token_stream()),
+            (&try_func_ident, try_output),
+        ];
+        for (cfg, _) in objc_attr.versions.unavailable() {
+            // The bound is higher-ranked so it's only checked where it's used.
+            let funcs = funcs.iter().map(|(func_ident, output)| {
+                quote::quote! {
+                    #(#attrs)*
+                    #[cfg(#cfg)]
+                    #[allow(unused_variables)]
+                    #vis unsafe #fn_token #func_ident #generics(#func_args) #output
+                    where
+                        for<'__a> &'__a #receiver_target_type: #trait_ident,
+                    {
+                        unreachable!()
+                    }
+                }
+            });
+            let stream = quote::quote! {
+                #(#funcs)*
+            };
+            tokens.extend(crate::assign_group_to_span(stream, *span))
+        }
+    }
+
+    fn try_func_ident(&self) -> syn::Ident {
+        syn::Ident::new(&format!("try_{}", self.ident), self.ident.span())
+    }
+
+    fn try_output(&self) -> proc_macro2::TokenStream {
+        let output_type = match &self.output {
+            syn::ReturnType::Default => quote::quote!(()),
+            syn::ReturnType::Type(_, t) => t.to_token_stream(),
+        };
+        quote::quote! {
+            -> Result<#output_type, objc_util::Unavailable>
+        }
+    }
+
+    /// Like the binding itself, but checks the running OS first instead of
+    /// only debug asserting on it.
+    fn try_func_impl(&self, tokens: &mut proc_macro2::TokenStream) {
+        let Self {
+            attrs,
+            objc_attr,
+            vis,
+            fn_token,
+            ident,
+            self_ty: _,
+            receiver,
+            superclass_arg: _,
+            args,
+            variadic,
+            output: _,
+            message_output: _,
+            output_kind: _,
+            span,
+        } = self;
+        let try_func_ident = self.try_func_ident();
+        let try_output = self.try_output();
+        let func_path = self.assoc_path(ident);
+        let receiver_arg = receiver.forward_arg();
+        let message_names = self
+            .superclass_arg
+            .iter()
+            .chain(args)
+            .map(|arg| &arg.name.ident);
+        let varargs = variadic.as_ref().map(|_| quote::quote!(varargs));
+        let func_args = self.func_args();
+        let generics = self.generics();
+        let cfgs = objc_attr.versions.os_cfgs();
+        let deprecated_attrs = objc_attr
+            .versions
+            .deprecated_attrs(|var| std::env::var(var).ok());
+        let availability_check = objc_attr.versions.availability_check();
+        let stream = quote::quote! {
+            #(#attrs)*
+            #cfgs
+            #deprecated_attrs
+            #vis unsafe #fn_token #try_func_ident #generics(#func_args) #try_output {
+                #availability_check
+                #[allow(deprecated)]
+                let result = #func_path(#receiver_arg #(#message_names,)* #varargs);
+                Ok(result)
             }
         };
         tokens.extend(crate::assign_group_to_span(stream, *span))
@@ -313,17 +1005,26 @@ impl MsgWrapper {
             vis,
             fn_token,
             ident,
+            self_ty: _,
             receiver: _,
+            superclass_arg: _,
             args: _,
+            variadic: _,
             output: _,
+            message_output: _,
+            output_kind: _,
             span,
         } = self;
         let sel_func_ident = self.sel_func_ident();
-        let sel_body = objc_attr.objc_meth_name.selector_func_body();
+        let sel_body = objc_attr
+            .objc_meth_name
+            .selector_func_body(objc_attr.lookup);
         let cfgs = self.objc_attr.versions.os_cfgs();
         let debug_assert_stmt = self.objc_attr.versions.debug_assert_stmt(ident);
+        // Derived selectors name bindings like `data_with_bytes__length`.
         let stream = quote::quote! {
             #cfgs
+            #[allow(non_snake_case)]
             #vis #fn_token #sel_func_ident() -> objc_util::objc::runtime::Sel {
                 #debug_assert_stmt
                 #sel_body
@@ -339,9 +1040,14 @@ impl MsgWrapper {
             vis,
             fn_token,
             ident,
+            self_ty: _,
             receiver: _,
+            superclass_arg: _,
             args: _,
+            variadic: _,
             output: _,
+            message_output: _,
+            output_kind: _,
             span,
         } = self;
         let cfgs = self.objc_attr.versions.supported_check();
@@ -358,40 +1064,560 @@ impl MsgWrapper {
     }
 
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        self.func_impl(tokens);
+        self.func_impl(tokens, false);
+        self.func_impl(tokens, true);
+        self.try_func_impl(tokens);
+        self.unavailable_impl(tokens);
         self.sel_func_impl(tokens);
         self.supported_func_impl(tokens)
     }
 }
 
+/// Spells out `Self` as `self_ty`.
+fn replace_self(tokens: proc_macro2::TokenStream, self_ty: &syn::Type) -> proc_macro2::TokenStream {
+    tokens
+        .into_iter()
+        .map(|tree| match tree {
+            proc_macro2::TokenTree::Ident(ident) if ident == "Self" => self_ty.to_token_stream(),
+            proc_macro2::TokenTree::Group(group) => {
+                let mut replaced = proc_macro2::Group::new(
+                    group.delimiter(),
+                    replace_self(group.stream(), self_ty),
+                );
+                replaced.set_span(group.span());
+                proc_macro2::TokenTree::Group(replaced).into()
+            }
+            tree => tree.into(),
+        })
+        .collect()
+}
+
 struct MsgReceiver {
     attrs: Vec<syn::Attribute>,
+    kind: ReceiverKind,
     name: syn::PatIdent,
     type_: syn::Type,
 }
 
-impl TryFrom<(Vec<syn::Attribute>, syn::PatIdent, syn::Type)> for MsgReceiver {
+enum ReceiverKind {
+    /// A raw pointer argument.
+    Arg,
+    /// An `Id<T>` handed over to an `init` method, with its declared type.
+    Owned(syn::Type),
+    /// `&self` or `&mut self`, on bindings declared in an `impl` block.
+    SelfArg(syn::Receiver),
+    /// The class named by `class = "..."`, which isn't an argument at all,
+    /// and how it's looked up.
+    Class(syn::Ident, crate::Lookup),
+}
+
+impl TryFrom<(syn::FnArg, Option<&syn::Type>)> for MsgReceiver {
     type Error = syn::Error;
 
-    fn try_from(
-        (attrs, name, type_): (Vec<syn::Attribute>, syn::PatIdent, syn::Type),
-    ) -> parse::Result<Self> {
-        match type_ {
-            syn::Type::Ptr(_) => {}
-            other => {
+    fn try_from((arg, self_ty): (syn::FnArg, Option<&syn::Type>)) -> parse::Result<Self> {
+        let (attrs, pat, ty) = match (arg, self_ty) {
+            (syn::FnArg::Receiver(r), Some(self_ty)) => {
+                if r.reference.is_none() {
+                    return Err(syn::Error::new(
+                        r.span(),
+                        "ObjC bindings take `&self`, `&mut self` or a raw pointer receiver",
+                    ));
+                }
+                let type_ = if r.mutability.is_some() {
+                    syn::parse_quote!(*mut #self_ty)
+                } else {
+                    syn::parse_quote!(*const #self_ty)
+                };
+                return Ok(Self {
+                    attrs: r.attrs.clone(),
+                    name: pat_ident("__self", r.self_token.span),
+                    kind: ReceiverKind::SelfArg(r),
+                    type_,
+                });
+            }
+            (syn::FnArg::Receiver(r), None) => {
+                return Err(syn::Error::new(
+                    r.span(),
+                    "ObjC bindings with `self` are only supported in `impl` blocks",
+                ))
+            }
+            (syn::FnArg::Typed(syn::PatType { attrs, pat, ty, .. }), self_ty) => {
+                let ty = match self_ty {
+                    Some(self_ty) => syn::parse2(replace_self(ty.into_token_stream(), self_ty))?,
+                    None => *ty,
+                };
+                (attrs, pat, ty)
+            }
+        };
+        let name = match *pat {
+            syn::Pat::Ident(pat_ident) => pat_ident,
+            a => {
                 return Err(syn::Error::new(
-                    other.span(),
-                    "Objc message receivers must be raw pointers",
+                    a.span(),
+                    "ObjC bindings with destructuring is unsupported",
                 ))
             }
+        };
+        let (kind, type_) = match ty {
+            syn::Type::Ptr(_) => (ReceiverKind::Arg, ty),
+            ty => match pointee_ptr(&ty, "Id") {
+                Some(ptr) => (ReceiverKind::Owned(ty), ptr),
+                None => {
+                    return Err(syn::Error::new(
+                        ty.span(),
+                        "Objc message receivers must be raw pointers",
+                    ))
+                }
+            },
+        };
+        Ok(Self {
+            attrs,
+            kind,
+            name,
+            type_,
+        })
+    }
+}
+
+struct MsgArg {
+    attrs: Vec<syn::Attribute>,
+    kind: ArgKind,
+    name: syn::PatIdent,
+    /// The type taken by the wrapper.
+    type_: syn::Type,
+}
+
+enum ArgKind {
+    /// Sent as is.
+    Value,
+    /// `bool`, sent as the platform's `BOOL`.
+    Bool,
+    /// An `impl Fn(..)` closure, sent as this `objc_util::block::Block` type.
+    Block(syn::Type),
+}
+
+impl MsgArg {
+    fn new(
+        attrs: Vec<syn::Attribute>,
+        name: syn::PatIdent,
+        mut type_: syn::Type,
+    ) -> parse::Result<Self> {
+        let kind = match &mut type_ {
+            syn::Type::ImplTrait(impl_trait) => {
+                let block = block_type(impl_trait)?;
+                // The runtime clones the closure when it copies the block,
+                // and the copy may outlive the call.
+                impl_trait.bounds.push(syn::parse_quote!(Clone));
+                impl_trait.bounds.push(syn::parse_quote!('static));
+                ArgKind::Block(block)
+            }
+            ty if is_bool(ty) => ArgKind::Bool,
+            _ => ArgKind::Value,
+        };
+        Ok(Self {
+            attrs,
+            kind,
+            name,
+            type_,
+        })
+    }
+
+    /// The argument as declared on the wrapper.
+    fn func_arg(&self) -> proc_macro2::TokenStream {
+        let Self {
+            attrs, name, type_, ..
+        } = self;
+        quote::quote! {
+            #(#attrs)* #name: #type_,
         }
-        Ok(Self { attrs, name, type_ })
+    }
+
+    /// The argument as sent in the message.
+    fn decl_arg(&self, mock: bool) -> proc_macro2::TokenStream {
+        let Self { attrs, name, .. } = self;
+        let message_type = self.message_type(mock);
+        quote::quote! {
+            #(#attrs)* #name: #message_type,
+        }
+    }
+
+    fn message_type(&self, mock: bool) -> syn::Type {
+        match &self.kind {
+            ArgKind::Value => self.type_.clone(),
+            ArgKind::Bool => syn::parse_quote!(objc_util::runtime::BOOL),
+            // The mock runtime needs `'static` arguments.
+            ArgKind::Block(block) if mock => syn::parse_quote!(*const #block),
+            ArgKind::Block(block) => syn::parse_quote!(&#block),
+        }
+    }
+
+    /// `Encode` is implemented for every block reference.
+    fn encode_bound(&self, mock: bool) -> Option<proc_macro2::TokenStream> {
+        let message_type = self.message_type(mock);
+        match self.kind {
+            ArgKind::Value | ArgKind::Bool => {
+                Some(quote::quote!(#message_type: objc_util::Encode,))
+            }
+            ArgKind::Block(_) => None,
+        }
+    }
+
+    /// Converts the argument into its message type, shadowing it. The
+    /// `StackBlock` stays alive until the wrapper returns.
+    fn lower_stmt(&self, mock: bool) -> Option<proc_macro2::TokenStream> {
+        let ident = &self.name.ident;
+        let message_type = self.message_type(mock);
+        match self.kind {
+            ArgKind::Value => None,
+            ArgKind::Bool => Some(quote::quote! {
+                let #ident: #message_type = if #ident {
+                    objc_util::runtime::YES
+                } else {
+                    objc_util::runtime::NO
+                };
+            }),
+            ArgKind::Block(_) => Some(quote::quote! {
+                let #ident = objc_util::block::StackBlock::new(#ident);
+                let #ident: #message_type = &*#ident;
+            }),
+        }
+    }
+}
+
+/// How the message's return value becomes the wrapper's.
+enum OutputKind {
+    /// Returned as is.
+    Value,
+    /// A raw pointer flagged `nonnull`, debug asserted on.
+    Nonnull,
+    /// `bool`, returned as the platform's `BOOL`.
+    Bool,
+    /// `NonNull<T>`, returned as this `*mut T` and debug asserted on.
+    NonNull(syn::Type),
+    /// `Option<NonNull<T>>`, returned as this `*mut T` with nil as `None`.
+    OptionNonNull(syn::Type),
+    /// `Id<T>`, returned as this `*mut T`. Panics on nil.
+    Id(syn::Type),
+    /// `Option<Id<T>>`, returned as this `*mut T` with nil as `None`.
+    OptionId(syn::Type),
+    /// `Result<T, Id<E>>` for `throws` bindings, with the error returned
+    /// through a trailing `*mut *mut E` argument (the second field). `T` is
+    /// `()` for `Bool`, which fails on `NO`, or an `Id` or `NonNull`, which
+    /// fail on nil.
+    Throws(Box<OutputKind>, syn::Type),
+}
+
+impl OutputKind {
+    fn throws(
+        output: &syn::ReturnType,
+        span: proc_macro2::Span,
+        nullability: Option<(Nullability, proc_macro2::Span)>,
+    ) -> parse::Result<Self> {
+        if let Some((_, span)) = nullability {
+            return Err(syn::Error::new(
+                span,
+                "`throws` bindings return an `Err` for nil. Remove the nullability flag.",
+            ));
+        }
+        let result_args = match output {
+            syn::ReturnType::Type(_, ty) => result_args(ty),
+            syn::ReturnType::Default => None,
+        };
+        let success = result_args.and_then(|(ok, err)| {
+            let error = pointee_ptr(err, "Id")?;
+            let success = match ok {
+                syn::Type::Tuple(tuple) if tuple.elems.is_empty() => OutputKind::Bool,
+                ok => match (pointee_ptr(ok, "Id"), pointee_ptr(ok, "NonNull")) {
+                    (Some(ptr), _) => OutputKind::Id(ptr),
+                    (_, Some(ptr)) => OutputKind::NonNull(ptr),
+                    _ => return None,
+                },
+            };
+            Some(OutputKind::Throws(Box::new(success), error))
+        });
+        success.ok_or_else(|| {
+            let span = match output {
+                syn::ReturnType::Type(_, ty) => ty.span(),
+                syn::ReturnType::Default => span,
+            };
+            syn::Error::new(
+                span,
+                "`throws` bindings return `Result<(), Id<E>>`, `Result<Id<T>, Id<E>>` or `Result<NonNull<T>, Id<E>>`",
+            )
+        })
+    }
+
+    /// The kind of the successful result, for `throws` bindings.
+    fn success(&self) -> &Self {
+        match self {
+            OutputKind::Throws(success, _) => success,
+            kind => kind,
+        }
+    }
+
+    fn new(
+        output: &syn::ReturnType,
+        nullability: Option<(Nullability, proc_macro2::Span)>,
+    ) -> parse::Result<Self> {
+        let ty = match output {
+            syn::ReturnType::Default => None,
+            syn::ReturnType::Type(_, ty) => Some(&**ty),
+        };
+        let kind = match ty {
+            Some(ty) if is_bool(ty) => OutputKind::Bool,
+            Some(ty) => {
+                let option_arg = option_arg(ty);
+                if let Some(ptr) = pointee_ptr(ty, "NonNull") {
+                    OutputKind::NonNull(ptr)
+                } else if let Some(ptr) = option_arg.and_then(|ty| pointee_ptr(ty, "NonNull")) {
+                    OutputKind::OptionNonNull(ptr)
+                } else if let Some(ptr) = pointee_ptr(ty, "Id") {
+                    OutputKind::Id(ptr)
+                } else if let Some(ptr) = option_arg.and_then(|ty| pointee_ptr(ty, "Id")) {
+                    OutputKind::OptionId(ptr)
+                } else {
+                    OutputKind::Value
+                }
+            }
+            None => OutputKind::Value,
+        };
+        let (nullability, span) = match nullability {
+            Some(nullability) => nullability,
+            None => return Ok(kind),
+        };
+        match (nullability, kind) {
+            (Nullability::Nonnull, OutputKind::Value) if matches!(ty, Some(syn::Type::Ptr(_))) => {
+                Ok(OutputKind::Nonnull)
+            }
+            (Nullability::Nullable, OutputKind::Value) if matches!(ty, Some(syn::Type::Ptr(_))) => {
+                Ok(OutputKind::Value)
+            }
+            (Nullability::Nonnull, kind @ OutputKind::NonNull(_))
+            | (Nullability::Nonnull, kind @ OutputKind::Id(_))
+            | (Nullability::Nullable, kind @ OutputKind::OptionNonNull(_))
+            | (Nullability::Nullable, kind @ OutputKind::OptionId(_)) => Ok(kind),
+            (Nullability::Nonnull, OutputKind::OptionNonNull(_))
+            | (Nullability::Nonnull, OutputKind::OptionId(_)) => Err(syn::Error::new(
+                span,
+                "`nonnull` contradicts the `Option` return type",
+            )),
+            (Nullability::Nullable, OutputKind::NonNull(_))
+            | (Nullability::Nullable, OutputKind::Id(_)) => Err(syn::Error::new(
+                span,
+                "`nullable` contradicts the return type. Wrap it in an `Option`.",
+            )),
+            _ => Err(syn::Error::new(
+                span,
+                "Nullability only applies to pointer return types",
+            )),
+        }
+    }
+}
+
+/// `*mut T` for `NonNull<T>` or `Id<T>`, as named by `name`.
+fn pointee_ptr(ty: &syn::Type, name: &str) -> Option<syn::Type> {
+    match last_segment_arg(ty, name)? {
+        syn::GenericArgument::Type(pointee) => Some(syn::parse_quote!(*mut #pointee)),
+        _ => None,
+    }
+}
+
+/// `-> T` for a `catch_exceptions` binding returning `Result<T, Id<E>>`.
+fn caught_output(
+    output: &syn::ReturnType,
+    span: proc_macro2::Span,
+) -> parse::Result<syn::ReturnType> {
+    let (arrow, ty) = match output {
+        syn::ReturnType::Type(arrow, ty) => (arrow, &**ty),
+        syn::ReturnType::Default => {
+            return Err(syn::Error::new(
+                span,
+                "`catch_exceptions` bindings return `Result<T, Id<NSException>>`",
+            ))
+        }
+    };
+    match result_args(ty) {
+        Some((ok, err)) if pointee_ptr(err, "Id").is_some() => Ok(match ok {
+            syn::Type::Tuple(tuple) if tuple.elems.is_empty() => syn::ReturnType::Default,
+            ok => syn::ReturnType::Type(*arrow, Box::new(ok.clone())),
+        }),
+        _ => Err(syn::Error::new(
+            ty.span(),
+            "`catch_exceptions` bindings return `Result<T, Id<NSException>>`",
+        )),
+    }
+}
+
+/// `T` and `E` for `Result<T, E>`.
+fn result_args(ty: &syn::Type) -> Option<(&syn::Type, &syn::Type)> {
+    let segment = match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Result" {
+        return None;
+    }
+    let args = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 2 => &args.args,
+        _ => return None,
+    };
+    match (&args[0], &args[1]) {
+        (syn::GenericArgument::Type(ok), syn::GenericArgument::Type(err)) => Some((ok, err)),
+        _ => None,
+    }
+}
+
+/// `T` for `Option<T>`.
+fn option_arg(ty: &syn::Type) -> Option<&syn::Type> {
+    match last_segment_arg(ty, "Option")? {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
+
+/// The single generic argument of a path type ending in `name`.
+fn last_segment_arg<'a>(ty: &'a syn::Type, name: &str) -> Option<&'a syn::GenericArgument> {
+    let segment = match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != name {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => args.args.first(),
+        _ => None,
+    }
+}
+
+fn is_bool(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.qself.is_none() && path.path.is_ident("bool"),
+        _ => false,
+    }
+}
+
+/// The `objc_util::block::Block` type for an `impl Fn(..)` argument.
+fn block_type(impl_trait: &syn::TypeImplTrait) -> parse::Result<syn::Type> {
+    let fn_trait = impl_trait.bounds.iter().find_map(|bound| match bound {
+        syn::TypeParamBound::Trait(bound) => bound.path.segments.last(),
+        _ => None,
+    });
+    let segment = match fn_trait {
+        Some(segment) if segment.ident == "Fn" => segment,
+        Some(segment) if segment.ident == "FnMut" || segment.ident == "FnOnce" => {
+            return Err(syn::Error::new(
+                segment.ident.span(),
+                "Blocks may be called any number of times. Use `impl Fn(..)`.",
+            ))
+        }
+        _ => {
+            return Err(syn::Error::new(
+                impl_trait.span(),
+                "ObjC bindings only take `impl Fn(..)` arguments",
+            ))
+        }
+    };
+    let args = match &segment.arguments {
+        syn::PathArguments::Parenthesized(args) => args,
+        _ => {
+            return Err(syn::Error::new(
+                segment.span(),
+                "Expected `Fn(..)` arguments",
+            ))
+        }
+    };
+    let inputs = args.inputs.iter();
+    let output = match &args.output {
+        syn::ReturnType::Default => quote::quote!(()),
+        syn::ReturnType::Type(_, ty) => ty.to_token_stream(),
+    };
+    Ok(syn::parse_quote! {
+        objc_util::block::Block<(#(#inputs,)*), #output>
+    })
+}
+
+fn pat_ident(name: &str, span: proc_macro2::Span) -> syn::PatIdent {
+    syn::PatIdent {
+        attrs: Vec::new(),
+        by_ref: None,
+        mutability: None,
+        ident: syn::Ident::new(name, span),
+        subpat: None,
     }
 }
 
 impl MsgReceiver {
-    fn as_tuple(&self) -> (&Vec<syn::Attribute>, &syn::PatIdent, &syn::Type) {
-        (&self.attrs, &self.name, &self.type_)
+    fn class(class: syn::Ident, lookup: crate::Lookup) -> Self {
+        Self {
+            attrs: Vec::new(),
+            name: pat_ident("__class", class.span()),
+            kind: ReceiverKind::Class(class, lookup),
+            type_: syn::parse_quote!(*const objc_util::runtime::Class),
+        }
+    }
+
+    /// The receiver as declared on the wrapper.
+    fn func_arg(&self) -> proc_macro2::TokenStream {
+        match &self.kind {
+            ReceiverKind::Arg => self.decl_arg(),
+            ReceiverKind::Owned(ty) => {
+                let Self { attrs, name, .. } = self;
+                quote::quote! {
+                    #(#attrs)* #name: #ty,
+                }
+            }
+            ReceiverKind::SelfArg(self_arg) => quote::quote!(#self_arg,),
+            ReceiverKind::Class(..) => proc_macro2::TokenStream::new(),
+        }
+    }
+
+    /// The receiver as a raw pointer argument.
+    fn decl_arg(&self) -> proc_macro2::TokenStream {
+        let Self {
+            attrs, name, type_, ..
+        } = self;
+        match self.kind {
+            ReceiverKind::Class(..) => proc_macro2::TokenStream::new(),
+            _ => quote::quote! {
+                #(#attrs)* #name: #type_,
+            },
+        }
+    }
+
+    /// The receiver as passed on to another wrapper.
+    fn forward_arg(&self) -> proc_macro2::TokenStream {
+        match &self.kind {
+            ReceiverKind::Arg | ReceiverKind::Owned(_) => {
+                let ident = &self.name.ident;
+                quote::quote!(#ident,)
+            }
+            ReceiverKind::SelfArg(self_arg) => {
+                let self_token = &self_arg.self_token;
+                quote::quote!(#self_token,)
+            }
+            ReceiverKind::Class(..) => proc_macro2::TokenStream::new(),
+        }
+    }
+
+    /// The receiver as a raw pointer.
+    fn value(&self) -> proc_macro2::TokenStream {
+        let type_ = &self.type_;
+        match &self.kind {
+            ReceiverKind::Arg => self.name.ident.to_token_stream(),
+            ReceiverKind::Owned(_) => {
+                let ident = &self.name.ident;
+                quote::quote!(objc_util::Id::into_raw(#ident))
+            }
+            ReceiverKind::SelfArg(self_arg) => {
+                let self_token = &self_arg.self_token;
+                quote::quote!((#self_token as #type_))
+            }
+            ReceiverKind::Class(class, lookup) => {
+                let class = crate::class_func_body(class, *lookup);
+                quote::quote!((#class as #type_))
+            }
+        }
     }
 
     fn target_type(&self) -> &syn::Type {
@@ -401,3 +1627,107 @@ impl MsgReceiver {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::extern_objc::ExternObjc;
+    use quote::ToTokens;
+
+    fn tokens(tokens: impl ToTokens) -> String {
+        tokens
+            .to_token_stream()
+            .to_string()
+            .split_whitespace()
+            .collect()
+    }
+
+    /// The `cfg` and `diagnostic::on_unimplemented` attributes of an item.
+    fn attrs(attrs: &[syn::Attribute]) -> (String, String) {
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|attr| tokens(&attr.path) == name)
+                .map(|attr| tokens(&attr.tokens))
+                .unwrap_or_default()
+        };
+        (attr("cfg"), attr("diagnostic::on_unimplemented"))
+    }
+
+    #[test]
+    fn unavailable_diagnostics() {
+        let data: ExternObjc = syn::parse_quote! {
+            extern "ObjC" {
+                #[objc(selector = "homeDirectoryForUser:", macos = "10.12", ios = "unavailable")]
+                pub fn home_directory(
+                    obj: *const runtime::Object,
+                    user_name: *const runtime::Object,
+                ) -> *mut runtime::Object;
+            }
+        };
+        let expansion: syn::File = syn::parse2(data.into_token_stream()).unwrap();
+        let ios = "(all(target_os=\"ios\",not(target_abi=\"macabi\")))";
+        let catalyst = "(all(target_os=\"ios\",target_abi=\"macabi\"))";
+
+        let mut traits: Vec<_> = expansion
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Trait(item) => {
+                    assert_eq!(item.ident, "_unavailable_home_directory");
+                    Some(attrs(&item.attrs))
+                }
+                _ => None,
+            })
+            .collect();
+        traits.sort();
+        assert_eq!(
+            traits,
+            [
+                (
+                    ios.to_string(),
+                    "(message=\"`home_directory`isunavailableoniOS\",\
+                     label=\"unavailableoniOS\")"
+                        .to_string()
+                ),
+                (
+                    catalyst.to_string(),
+                    "(message=\"`home_directory`isunavailableonMacCatalyst\",\
+                     label=\"unavailableonMacCatalyst\")"
+                        .to_string()
+                ),
+            ]
+        );
+
+        // Both the binding and its `try_` wrapper have a stand-in per platform.
+        let mut stand_ins: Vec<_> = expansion
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Fn(item) => Some((
+                    item.sig.ident.to_string(),
+                    attrs(&item.attrs).0,
+                    tokens(&item.sig.generics.where_clause),
+                )),
+                _ => None,
+            })
+            .filter(|(_, cfg, _)| cfg == ios || cfg == catalyst)
+            .map(|(ident, cfg, bound)| {
+                assert_eq!(
+                    bound,
+                    "wherefor<'__a>&'__aruntime::Object:_unavailable_home_directory,"
+                );
+                (ident, cfg)
+            })
+            .collect();
+        stand_ins.sort();
+        assert_eq!(
+            stand_ins,
+            [
+                ("home_directory".to_string(), ios.to_string()),
+                ("home_directory".to_string(), catalyst.to_string()),
+                ("try_home_directory".to_string(), ios.to_string()),
+                ("try_home_directory".to_string(), catalyst.to_string()),
+            ]
+        );
+    }
+}
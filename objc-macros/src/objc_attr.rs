@@ -1,17 +1,83 @@
 use crate::{
     objc_selector::{ObjCMethName, ObjCSelector},
     os_versions::OSVersions,
+    Lookup,
 };
 use std::convert::{TryFrom, TryInto};
 use syn::{parse, spanned::Spanned};
 
+#[derive(Clone)]
 pub struct ObjCAttr {
-    pub objc_meth_name: ObjCMethName,
-    pub versions:       OSVersions,
-    span:               proc_macro2::Span,
+    /// The selector, or the getter of a `property`.
+    pub objc_meth_name:   ObjCMethName,
+    /// Set when the selector was derived from the binding's name.
+    pub derived_selector: bool,
+    pub versions:         OSVersions,
+    /// Set for class methods sent to a fixed class (`class = "NSData"`).
+    pub class:            Option<syn::Ident>,
+    /// The return value's nullability (`nullable` or `nonnull`), if given.
+    pub nullability:      Option<(Nullability, proc_macro2::Span)>,
+    /// Overrides the ownership of the result implied by the method family
+    /// (`returns_retained` or `returns_autoreleased`).
+    pub ownership:        Option<(Ownership, proc_macro2::Span)>,
+    /// Set when the method reports failure through a trailing `error:`
+    /// argument (`throws`).
+    pub throws:           Option<proc_macro2::Span>,
+    /// Set when the message is sent inside `exception::catch`
+    /// (`catch_exceptions`).
+    pub catch_exceptions: Option<proc_macro2::Span>,
+    /// Set when the message is sent to `super`.
+    pub superclass:       Option<Superclass>,
+    /// The setter of a writable `property`.
+    pub setter:           Option<ObjCMethName>,
+    /// How the selector and classes are looked up (`lookup = "runtime"` or
+    /// `"static"`), defaulting to the crate feature.
+    pub lookup:           Lookup,
+    span:                 proc_macro2::Span,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Nullability {
+    Nullable,
+    Nonnull,
+}
+
+#[derive(Clone)]
+pub enum Superclass {
+    /// `super`: the binding takes the superclass after the receiver.
+    Arg,
+    /// `super = "NSView"`
+    Named(syn::Ident),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Ownership {
+    Retained,
+    Autoreleased,
 }
 
 impl ObjCAttr {
+    /// Whether the caller owns the returned object.
+    pub fn returns_retained(&self) -> bool {
+        match self.ownership {
+            Some((ownership, _)) => ownership == Ownership::Retained,
+            None => self.objc_meth_name.family().returns_retained(),
+        }
+    }
+
+    /// The attribute of the setter generated for a writable `property`.
+    pub fn setter_attr(&self) -> Option<Self> {
+        let setter = self.setter.clone()?;
+        Some(Self {
+            objc_meth_name: setter,
+            nullability: None,
+            ownership: None,
+            derived_selector: false,
+            setter: None,
+            ..self.clone()
+        })
+    }
+
     pub fn is_objc(attr: &syn::Attribute) -> bool {
         match attr.path.get_ident() {
             Some(ident) => {
@@ -32,10 +98,10 @@ impl Spanned for ObjCAttr {
     }
 }
 
-impl TryFrom<syn::Attribute> for ObjCAttr {
+impl TryFrom<(syn::Attribute, &syn::Signature)> for ObjCAttr {
     type Error = syn::Error;
 
-    fn try_from(attr: syn::Attribute) -> parse::Result<Self> {
+    fn try_from((attr, sig): (syn::Attribute, &syn::Signature)) -> parse::Result<Self> {
         let span = attr.span();
         match attr.style {
             syn::AttrStyle::Inner(_) => {
@@ -55,20 +121,271 @@ impl TryFrom<syn::Attribute> for ObjCAttr {
                 ))
             }
         };
-        if list.nested.len() < 2 {
+        let nested_span = list.nested.span();
+        let mut class = None;
+        let mut nullability = None;
+        let mut ownership = None;
+        let mut throws = None;
+        let mut catch_exceptions = None;
+        let mut superclass = None;
+        let mut property = None;
+        let mut getter = None;
+        let mut setter = None;
+        let mut readonly = None;
+        let mut lookup = None;
+        let mut nested = syn::punctuated::Punctuated::<_, syn::token::Comma>::new();
+        for nested_meta in list.nested {
+            match nested_meta {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("class") => {
+                    if class.is_some() {
+                        return Err(syn::Error::new(nv.path.span(), "Duplicate `class` keys"));
+                    }
+                    class = Some(class_ident(nv.lit)?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("super") => {
+                    if superclass.is_some() {
+                        return Err(syn::Error::new(nv.path.span(), "Duplicate `super` keys"));
+                    }
+                    superclass = Some(Superclass::Named(class_ident(nv.lit)?));
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("super") => {
+                    if superclass.is_some() {
+                        return Err(syn::Error::new(path.span(), "Duplicate `super` keys"));
+                    }
+                    superclass = Some(Superclass::Arg);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("lookup") => {
+                    if lookup.is_some() {
+                        return Err(syn::Error::new(nv.path.span(), "Duplicate `lookup` keys"));
+                    }
+                    lookup = Some(Lookup::from_lit(&nv.lit)?);
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                    if is_accessor_key(&nv.path) =>
+                {
+                    let key = if nv.path.is_ident("property") {
+                        &mut property
+                    } else if nv.path.is_ident("getter") {
+                        &mut getter
+                    } else {
+                        &mut setter
+                    };
+                    if key.is_some() {
+                        return Err(syn::Error::new(
+                            nv.path.span(),
+                            format!(
+                                "Duplicate `{}` keys",
+                                nv.path.get_ident().unwrap()
+                            ),
+                        ));
+                    }
+                    *key = Some((nv.path.span(), nv.lit));
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path)) if is_flag(&path) => {
+                    let span = path.span();
+                    if path.is_ident("readonly") {
+                        if readonly.is_some() {
+                            return Err(syn::Error::new(span, "Duplicate `readonly` flags"));
+                        }
+                        readonly = Some(span);
+                    } else if path.is_ident("nullable") || path.is_ident("nonnull") {
+                        if nullability.is_some() {
+                            return Err(syn::Error::new(span, "Duplicate nullability flags"));
+                        }
+                        let flag = if path.is_ident("nullable") {
+                            Nullability::Nullable
+                        } else {
+                            Nullability::Nonnull
+                        };
+                        nullability = Some((flag, span));
+                    } else if path.is_ident("throws") {
+                        if throws.is_some() {
+                            return Err(syn::Error::new(span, "Duplicate `throws` flags"));
+                        }
+                        throws = Some(span);
+                    } else if path.is_ident("catch_exceptions") {
+                        if catch_exceptions.is_some() {
+                            return Err(syn::Error::new(
+                                span,
+                                "Duplicate `catch_exceptions` flags",
+                            ));
+                        }
+                        catch_exceptions = Some(span);
+                    } else {
+                        if ownership.is_some() {
+                            return Err(syn::Error::new(span, "Duplicate ownership flags"));
+                        }
+                        let flag = if path.is_ident("returns_retained") {
+                            Ownership::Retained
+                        } else {
+                            Ownership::Autoreleased
+                        };
+                        ownership = Some((flag, span));
+                    }
+                }
+                nested_meta => nested.push(nested_meta),
+            }
+        }
+        if let (Some(class), Some(_)) = (&class, &superclass) {
             return Err(syn::Error::new(
-                list.nested.span(),
-                "Expected `selector = \"xx:xx:xx:\", OS_NAME = \"#-#-#\"`.",
+                class.span(),
+                "Bindings with a `class` can't message `super`",
             ));
         }
-        let mut iter = list.nested.into_iter();
-        let sig: ObjCSelector = iter.next().unwrap().try_into()?;
-        let versions = iter.try_into()?;
+        let mut derived_selector = false;
+        let (objc_meth_name, setter, versions) = match property {
+            Some((_, name)) => {
+                if let Some(span) = throws {
+                    return Err(syn::Error::new(span, "Properties can't `throws`"));
+                }
+                if let (Some(span), None) = (catch_exceptions, readonly) {
+                    return Err(syn::Error::new(
+                        span,
+                        "Only `readonly` properties can `catch_exceptions`. Declare the setter separately.",
+                    ));
+                }
+                if let Some(syn::NestedMeta::Meta(syn::Meta::NameValue(nv))) = nested.first() {
+                    if nv.path.is_ident("selector") {
+                        return Err(syn::Error::new(
+                            nv.path.span(),
+                            "Properties take no `selector`. Rename the accessors with `getter` and `setter`.",
+                        ));
+                    }
+                }
+                if nested.is_empty() {
+                    return Err(syn::Error::new(
+                        nested_span,
+                        "Expected `property = \"name\", OS_NAME = \"#-#-#\"`.",
+                    ));
+                }
+                let property: ObjCMethName = name.try_into()?;
+                if property.arg_count() != 1 {
+                    return Err(syn::Error::new(
+                        property.span(),
+                        "Expected a property name (e.g. `\"frame\"`)",
+                    ));
+                }
+                let getter = match getter {
+                    Some((_, lit)) => {
+                        let getter: ObjCMethName = lit.try_into()?;
+                        if getter.arg_count() != 1 {
+                            return Err(syn::Error::new(
+                                getter.span(),
+                                "Property getters take no arguments (e.g. `\"isHidden\"`)",
+                            ));
+                        }
+                        getter
+                    }
+                    None => property.clone(),
+                };
+                let setter = match (setter, readonly) {
+                    (Some((span, _)), Some(_)) => {
+                        return Err(syn::Error::new(
+                            span,
+                            "`readonly` properties have no setter",
+                        ))
+                    }
+                    (Some((_, lit)), None) => {
+                        let setter: ObjCMethName = lit.try_into()?;
+                        if setter.arg_count() != 2 {
+                            return Err(syn::Error::new(
+                                setter.span(),
+                                "Property setters take one argument (e.g. `\"setFrame:\"`)",
+                            ));
+                        }
+                        Some(setter)
+                    }
+                    (None, None) => Some(property.default_setter()),
+                    (None, Some(_)) => None,
+                };
+                (getter, setter, nested.into_iter().try_into()?)
+            }
+            None => {
+                let accessor_span = getter
+                    .or(setter)
+                    .map(|(span, _)| span)
+                    .or(readonly);
+                if let Some(span) = accessor_span {
+                    return Err(syn::Error::new(
+                        span,
+                        "Only properties have accessors. Add a `property` key.",
+                    ));
+                }
+                let has_selector = match nested.first() {
+                    Some(syn::NestedMeta::Meta(syn::Meta::NameValue(nv))) => {
+                        nv.path.is_ident("selector")
+                    }
+                    _ => false,
+                };
+                if nested.len() < 1 + has_selector as usize {
+                    return Err(syn::Error::new(
+                        nested_span,
+                        "Expected `selector = \"xx:xx:xx:\", OS_NAME = \"#-#-#\"`.",
+                    ));
+                }
+                let mut iter = nested.into_iter();
+                let objc_meth_name = if has_selector {
+                    let sel: ObjCSelector = iter.next().unwrap().try_into()?;
+                    sel.objc_meth_name
+                } else {
+                    // Arguments other than the receiver and superclass, or
+                    // the hidden `error:`.
+                    let superclass_arg_count =
+                        matches!(superclass, Some(Superclass::Arg)) as usize;
+                    let arg_count = (sig.inputs.len() + class.is_some() as usize)
+                        .saturating_sub(1 + superclass_arg_count);
+                    let takes_args =
+                        arg_count > 0 || throws.is_some() || sig.variadic.is_some();
+                    derived_selector = true;
+                    ObjCMethName::from_fn_name(&sig.ident, takes_args)?
+                };
+                (objc_meth_name, None, iter.try_into()?)
+            }
+        };
 
         Ok(Self {
-            objc_meth_name: sig.objc_meth_name,
+            objc_meth_name,
+            derived_selector,
             versions,
+            class,
+            nullability,
+            ownership,
+            throws,
+            catch_exceptions,
+            superclass,
+            setter,
+            lookup: lookup.unwrap_or_default(),
             span,
         })
     }
 }
+
+fn is_flag(path: &syn::Path) -> bool {
+    [
+        "nullable",
+        "nonnull",
+        "returns_retained",
+        "returns_autoreleased",
+        "throws",
+        "catch_exceptions",
+        "readonly",
+    ]
+    .iter()
+    .any(|flag| path.is_ident(flag))
+}
+
+fn is_accessor_key(path: &syn::Path) -> bool {
+    ["property", "getter", "setter"]
+        .iter()
+        .any(|key| path.is_ident(key))
+}
+
+fn class_ident(lit: syn::Lit) -> parse::Result<syn::Ident> {
+    match &lit {
+        syn::Lit::Str(s) => s.parse(),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            "Expected an ObjC class name (e.g. `\"NSData\"`)",
+        )),
+    }
+}
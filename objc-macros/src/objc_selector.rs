@@ -1,9 +1,9 @@
-use crate::calculate_hash;
-use std::convert::{TryFrom, TryInto};
-use syn::{
-    parse::{self, Parse, ParseStream},
-    spanned::Spanned,
+use crate::{symbol, Lookup};
+use std::{
+    convert::{TryFrom, TryInto},
+    ops::Range,
 };
+use syn::{parse, spanned::Spanned};
 
 pub struct ObjCSelector {
     pub objc_meth_name: ObjCMethName,
@@ -29,8 +29,39 @@ impl TryFrom<syn::NestedMeta> for ObjCSelector {
     }
 }
 
+/// The ARC method families, which decide who owns the objects a method
+/// returns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MethodFamily {
+    Alloc,
+    Copy,
+    MutableCopy,
+    New,
+    Init,
+    None,
+}
+
+impl MethodFamily {
+    /// Whether the caller owns the result (+1). Methods outside a family
+    /// return autoreleased objects.
+    pub fn returns_retained(self) -> bool {
+        self != MethodFamily::None
+    }
+
+    /// Whether the method takes over the reference to its receiver, as `init`
+    /// methods do.
+    pub fn consumes_receiver(self) -> bool {
+        self == MethodFamily::Init
+    }
+}
+
+/// A selector as the runtime registers it: a unary `hash`, or keywords each
+/// ending in a `:`, which may be empty (`setValue::`, `:`). Keywords are
+/// ASCII identifiers, `$` and Rust keywords included.
+#[derive(Clone)]
 pub struct ObjCMethName {
-    name: syn::punctuated::Punctuated<syn::Ident, syn::token::Colon>,
+    selector: String,
+    span: proc_macro2::Span,
 }
 
 impl TryFrom<syn::Lit> for ObjCMethName {
@@ -38,7 +69,10 @@ impl TryFrom<syn::Lit> for ObjCMethName {
 
     fn try_from(lit: syn::Lit) -> parse::Result<Self> {
         match lit {
-            syn::Lit::Str(s) => s.parse(),
+            syn::Lit::Str(s) => {
+                let token = s.token();
+                Self::lex(s.value(), s.span(), |range| lit_subspan(&s, &token, range))
+            }
             o => Err(syn::Error::new(
                 o.span(),
                 "Expected an ObjC method name (e.g. `fooBar:with:`)",
@@ -49,52 +83,144 @@ impl TryFrom<syn::Lit> for ObjCMethName {
 
 impl Spanned for ObjCMethName {
     fn span(&self) -> proc_macro2::Span {
-        self.name.span()
+        self.span
     }
 }
 
-impl Parse for ObjCMethName {
-    fn parse(input: ParseStream) -> parse::Result<Self> {
-        let name = syn::punctuated::Punctuated::parse_terminated(input)?;
-        if name.len() == 0 || name.len() > 1 && !name.trailing_punct() {
-            Err(syn::Error::new(
-                name.span(),
-                "Invalid ObjC method name. Are you missing a trailing `:`?",
-            ))
-        } else {
-            Ok(Self { name })
+impl ObjCMethName {
+    /// Checks `selector`, pointing errors at the offending characters with
+    /// `subspan` where the span of the source is known.
+    fn lex(
+        selector: String,
+        span: proc_macro2::Span,
+        subspan: impl Fn(Range<usize>) -> Option<proc_macro2::Span>,
+    ) -> parse::Result<Self> {
+        let error = |range: Range<usize>, message: String| {
+            syn::Error::new(subspan(range).unwrap_or(span), message)
+        };
+        if selector.is_empty() {
+            return Err(error(0..0, "Empty ObjC method name".to_string()));
+        }
+        let mut keyword_start = 0;
+        for (i, c) in selector.char_indices() {
+            let range = i..i + c.len_utf8();
+            match c {
+                ':' => keyword_start = range.end,
+                c if c.is_ascii_digit() && i == keyword_start => {
+                    return Err(error(
+                        range,
+                        format!(
+                            "ObjC method name keywords can't start with a digit (`{}` at offset {})",
+                            c, i
+                        ),
+                    ))
+                }
+                c if c == '_' || c == '$' || c.is_ascii_alphanumeric() => {}
+                c => {
+                    return Err(error(
+                        range,
+                        format!(
+                            "Invalid character `{}` at offset {} of the ObjC method name",
+                            c.escape_debug(),
+                            i
+                        ),
+                    ))
+                }
+            }
         }
+        if keyword_start > 0 && keyword_start < selector.len() {
+            return Err(error(
+                keyword_start..selector.len(),
+                "Invalid ObjC method name. Are you missing a trailing `:`?".to_string(),
+            ));
+        }
+        Ok(Self { selector, span })
     }
-}
 
-impl ObjCMethName {
     pub fn arg_count(&self) -> usize {
-        self.name.len() - (!self.name.trailing_punct()) as usize + 1
+        self.selector.matches(':').count() + 1
     }
 
-    pub fn as_string(&self) -> String {
-        self.name
-            .pairs()
-            .map(|pair| {
-                if pair.punct().is_some() {
-                    format!("{}:", pair.value())
-                } else {
-                    format!("{}", pair.value())
-                }
+    /// The method's ARC family, following clang's naming rules: the first
+    /// keyword, ignoring leading underscores, is the family name or starts
+    /// with it followed by a character that isn't a lowercase letter.
+    /// `copyItem` and `init_` are in a family, `copyright` and `initials`
+    /// aren't.
+    pub fn family(&self) -> MethodFamily {
+        let first = self.selector.split(':').next().unwrap();
+        let first = first.trim_start_matches('_');
+        let families = [
+            ("alloc", MethodFamily::Alloc),
+            ("copy", MethodFamily::Copy),
+            ("mutableCopy", MethodFamily::MutableCopy),
+            ("new", MethodFamily::New),
+            ("init", MethodFamily::Init),
+        ];
+        families
+            .iter()
+            .find(|(name, _)| match first.strip_prefix(name) {
+                Some(rest) => !rest.starts_with(|c: char| c.is_ascii_lowercase()),
+                None => false,
             })
-            .collect()
+            .map_or(MethodFamily::None, |&(_, family)| family)
+    }
+
+    /// Derives the selector from a binding's name: `__` separates keywords,
+    /// which are camel-cased, so `data_with_bytes__length` is
+    /// `dataWithBytes:length:` and `hash` is `hash`. Keywords end with a `:`
+    /// when the message takes arguments.
+    pub fn from_fn_name(ident: &syn::Ident, takes_args: bool) -> parse::Result<Self> {
+        let name = ident.to_string();
+        let name = name.trim_start_matches("r#");
+        let keywords = name.split("__").collect::<Vec<_>>();
+        if keywords.iter().any(|keyword| keyword.is_empty()) {
+            return Err(syn::Error::new(
+                ident.span(),
+                "Can't derive a selector from this name. Add a `selector` key.",
+            ));
+        }
+        let mut selector = String::new();
+        for keyword in &keywords {
+            selector.push_str(&camel_case(keyword));
+            if takes_args || keywords.len() > 1 {
+                selector.push(':');
+            }
+        }
+        Self::lex(selector, ident.span(), |_| None)
+    }
+
+    /// The setter implied by a property name: `frame` is set with
+    /// `setFrame:`.
+    pub fn default_setter(&self) -> Self {
+        let mut chars = self.selector.chars();
+        let first = chars.next().unwrap().to_ascii_uppercase();
+        Self {
+            selector: format!("set{}{}:", first, chars.as_str()),
+            span: self.span,
+        }
+    }
+
+    pub fn as_string(&self) -> String {
+        self.selector.clone()
     }
 
-    pub fn selector_func_body(&self) -> proc_macro2::TokenStream {
-        if cfg!(feature = "compile-time") {
+    pub fn selector_func_body(&self, lookup: Lookup) -> proc_macro2::TokenStream {
+        let selector = syn::LitStr::new(&self.as_string(), self.span());
+        crate::mock_or_real(
+            quote::quote! {
+                objc_util::mock::sel(#selector)
+            },
+            self.real_selector_func_body(lookup),
+        )
+    }
+
+    fn real_selector_func_body(&self, lookup: Lookup) -> proc_macro2::TokenStream {
+        if lookup == Lookup::Static {
             let mut selector_string = self.as_string();
-            let random_id = &format!(
-                "{}",
-                calculate_hash(&format!("{}{:?}", selector_string, self.span()))
-            );
+            let symbol_id = &symbol::symbol_id("sel", &selector_string, self.span());
             let meth_name_export_name = [
                 "\x01L_OBJC_METH_VAR_NAME_.__objc_util_meth.",
-                random_id,
+                symbol_id,
                 ".",
                 &selector_string,
             ]
@@ -102,7 +228,7 @@ impl ObjCMethName {
 
             let sel_ref_export_name = [
                 "\x01L_OBJC_SELECTOR_REFERENCES_.__objc_util_sel.",
-                random_id,
+                symbol_id,
                 ".",
                 &selector_string,
             ]
@@ -111,7 +237,7 @@ impl ObjCMethName {
             let selector = syn::LitByteStr::new(selector_string.as_bytes(), self.span());
             let selector_len = selector_string.len();
 
-            quote::quote! {
+            let body = quote::quote! {
                 #[link_section = "__TEXT,__objc_methname,cstring_literals"]
                 #[export_name = #meth_name_export_name]
                 static METH_NAME: [u8; #selector_len] = * #selector;
@@ -126,17 +252,141 @@ impl ObjCMethName {
                     ))
                 };
                 sel
-            }
+            };
+            Lookup::static_or_error(body, self.span())
         } else {
-            let selector = self.as_string();
-            let lit = syn::LitStr::new(&selector, self.span());
-            let tokens = lit.parse::<proc_macro2::TokenStream>().unwrap();
+            // `sel!` only takes identifiers, so empty keywords go straight to
+            // the registering macro underneath it.
+            let selector = syn::LitStr::new(&format!("{}\0", self.selector), self.span());
             quote::quote! {
-                {
-                    use objc_util::objc::sel_impl;
-                    objc_util::objc::sel!(#tokens)
-                }
+                objc_util::objc::sel_impl!(#selector)
             }
         }
     }
 }
+
+/// `data_with_bytes` to `dataWithBytes`, keeping leading underscores.
+fn camel_case(snake_case: &str) -> String {
+    let words = snake_case.trim_start_matches('_');
+    let mut camel_case = snake_case[..snake_case.len() - words.len()].to_string();
+    for (i, word) in words.split('_').enumerate() {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => camel_case.push(first.to_ascii_uppercase()),
+            Some(first) => camel_case.push(first),
+            None => {}
+        }
+        camel_case.push_str(chars.as_str());
+    }
+    camel_case
+}
+
+/// The span of `range` of `lit`'s value, if the literal spells it out
+/// without escapes and the compiler supports subspans.
+fn lit_subspan(
+    lit: &syn::LitStr,
+    token: &proc_macro2::Literal,
+    range: Range<usize>,
+) -> Option<proc_macro2::Span> {
+    let source = token.to_string();
+    let start = source.find('"')? + 1;
+    let end = source.rfind('"')?;
+    if source.get(start..end)? != lit.value() {
+        return None;
+    }
+    token.subspan(start + range.start..start + range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lexes `selector` as written in a string literal.
+    fn lex(selector: &str) -> parse::Result<ObjCMethName> {
+        syn::parse_str::<syn::Lit>(&format!("{:?}", selector))?.try_into()
+    }
+
+    /// The error lexing `selector` and the column it points at, where the
+    /// literal's opening quote is column 0.
+    fn error(selector: &str) -> (String, usize) {
+        match lex(selector) {
+            Ok(_) => panic!("`{}` lexed", selector),
+            Err(error) => (error.to_string(), error.span().start().column),
+        }
+    }
+
+    #[test]
+    fn empty_keywords() {
+        for &(selector, arg_count) in &[("hash", 1), (":", 2), ("::", 3), ("setValue::", 3)] {
+            let meth_name = lex(selector).unwrap();
+            assert_eq!(meth_name.as_string(), selector);
+            assert_eq!(meth_name.arg_count(), arg_count);
+        }
+    }
+
+    #[test]
+    fn digit_start() {
+        assert!(lex("set2:").is_ok());
+        assert_eq!(
+            error("value:2x:"),
+            (
+                "ObjC method name keywords can't start with a digit (`2` at offset 6)".to_string(),
+                7
+            )
+        );
+    }
+
+    #[test]
+    fn missing_trailing_colon() {
+        assert_eq!(
+            error("setValue:forKey"),
+            (
+                "Invalid ObjC method name. Are you missing a trailing `:`?".to_string(),
+                10
+            )
+        );
+    }
+
+    #[test]
+    fn subspans() {
+        assert_eq!(
+            error("foo:b@r:"),
+            (
+                "Invalid character `@` at offset 5 of the ObjC method name".to_string(),
+                6
+            )
+        );
+        assert_eq!(error("").1, 1);
+        assert_eq!(
+            error("\u{663}foo:"),
+            (
+                "Invalid character `\u{663}` at offset 0 of the ObjC method name".to_string(),
+                1
+            )
+        );
+        assert_eq!(error("caf\u{e9}:").1, 4);
+        // Escapes shift the offsets, so the whole literal is blamed.
+        assert_eq!(error("a\tb:c@:").1, 0);
+    }
+
+    #[test]
+    fn families() {
+        let families = [
+            ("copyright", MethodFamily::None),
+            ("copyItem", MethodFamily::Copy),
+            ("copy", MethodFamily::Copy),
+            ("mutableCopyWithZone:", MethodFamily::MutableCopy),
+            ("_init", MethodFamily::Init),
+            ("init_", MethodFamily::Init),
+            ("initWithBytes:length:", MethodFamily::Init),
+            ("initialize", MethodFamily::None),
+            ("newton", MethodFamily::None),
+            ("new", MethodFamily::New),
+            ("allocWithZone:", MethodFamily::Alloc),
+            ("hash", MethodFamily::None),
+        ];
+        for &(selector, family) in &families {
+            assert_eq!(lex(selector).unwrap().family(), family, "{}", selector);
+        }
+    }
+}
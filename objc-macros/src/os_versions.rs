@@ -1,6 +1,7 @@
 use std::{
     collections::HashMap,
     convert::{TryFrom, TryInto},
+    fmt,
 };
 use syn::{
     parse,
@@ -8,9 +9,9 @@ use syn::{
     spanned::Spanned,
 };
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct OSVersions {
-    versions: HashMap<OS, (Version, proc_macro2::Span)>,
+    versions: HashMap<OS, OSVersion>,
 }
 
 impl TryFrom<syn::punctuated::IntoIter<syn::NestedMeta>> for OSVersions {
@@ -40,58 +41,139 @@ impl OSVersions {
         Default::default()
     }
 
-    fn insert(&mut self, OSVersion { os, version, span }: OSVersion) -> Option<OSVersion> {
-        self.versions
-            .insert(os, (version, span))
-            .map(|old| OSVersion {
-                os,
-                version: old.0,
-                span: old.1,
-            })
+    fn insert(&mut self, os_version: OSVersion) -> Option<OSVersion> {
+        self.versions.insert(os_version.os, os_version)
+    }
+
+    /// Every OS the binding is declared for. As with clang's availability
+    /// attributes, Mac Catalyst inherits the `ios` key unless it has its own.
+    fn effective(&self) -> impl Iterator<Item = OSVersion> + '_ {
+        let implied_catalyst = match self.versions.get(&OS::iOS) {
+            Some(&ios) if !self.versions.contains_key(&OS::macCatalyst) => Some(OSVersion {
+                os: OS::macCatalyst,
+                ..ios
+            }),
+            _ => None,
+        };
+        self.versions.values().copied().chain(implied_catalyst)
+    }
+
+    fn available(&self) -> impl Iterator<Item = OSVersion> + '_ {
+        self.effective().filter(|os_version| !os_version.unavailable)
     }
 
     pub fn os_cfgs(&self) -> proc_macro2::TokenStream {
-        let cfgs = self.versions.keys().map(|key| key.as_nv_cfg());
+        let cfgs = self
+            .available()
+            .map(|os_version| os_version.os.as_nv_cfg())
+            .chain(std::iter::once(foreign_runtime_cfg()));
         quote::quote! {
             #[cfg(any(#(#cfgs),*))]
         }
     }
 
+    /// `#[deprecated]` attributes for every OS whose deployment target is at or
+    /// past the deprecation version. `env` looks up variables like
+    /// `MACOSX_DEPLOYMENT_TARGET`; without a deployment target nothing is
+    /// deprecated. Cargo doesn't see proc macros read the environment, so a
+    /// crate whose deprecations should follow it needs a `build.rs` printing
+    /// `cargo:rerun-if-env-changed` for those variables.
+    pub fn deprecated_attrs(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> proc_macro2::TokenStream {
+        self.available()
+            .filter_map(|os_version| os_version.deprecated_attr(&env))
+            .collect()
+    }
+
     pub fn supported_check(&self) -> proc_macro2::TokenStream {
-        let checks = self.versions.iter().map(|(&os, &(version, _))| {
-            OSVersion {
-                os,
-                version,
-                span: proc_macro2::Span::call_site(),
-            }
-            .supported_check()
-        });
+        let checks = self.effective().map(|os_version| os_version.supported_check());
+        let foreign_runtime_cfg = foreign_runtime_cfg();
         quote::quote! {
-            #(#checks else)* {
+            if cfg!(#foreign_runtime_cfg) {
+                true
+            } else #(#checks else)* {
                 false
             }
         }
     }
 
     pub fn debug_assert_stmt(&self, func_name: &syn::Ident) -> proc_macro2::TokenStream {
-        let checks = self.versions.iter().map(|(&os, &(version, _))| {
-            OSVersion {
-                os,
-                version,
-                span: proc_macro2::Span::call_site(),
-            }
-            .debug_assert_stmt(func_name)
-        });
+        let checks = self
+            .available()
+            .map(|os_version| os_version.debug_assert_stmt(func_name));
         quote::quote! {
             #(#checks)else*
         }
     }
+
+    /// Statements returning `Err(objc_util::Unavailable)` from the enclosing
+    /// function when the running OS doesn't provide the binding.
+    pub fn availability_check(&self) -> proc_macro2::TokenStream {
+        self.available()
+            .map(|os_version| os_version.availability_check())
+            .collect()
+    }
+
+    /// The cfg and display name of every OS the binding is marked unavailable on.
+    pub fn unavailable(
+        &self,
+    ) -> impl Iterator<Item = (proc_macro2::TokenStream, &'static str)> + '_ {
+        self.effective()
+            .filter(|os_version| os_version.unavailable)
+            .map(|os_version| (os_version.os.as_nv_cfg(), os_version.os.display_name()))
+    }
 }
 
+/// A `cfg` predicate matching the targets whose deployment target is at
+/// least the version `minimums` gives for their OS (e.g. `("macos", "10.15")`).
+/// `env` looks up variables like `MACOSX_DEPLOYMENT_TARGET`. OSes without a
+/// deployment target don't match, since rustc's default may be older.
+pub fn deployment_target_cfg(
+    minimums: &[(&str, &str)],
+    env: impl Fn(&str) -> Option<String>,
+) -> proc_macro2::TokenStream {
+    let cfgs = minimums.iter().filter_map(|&(os, minimum)| {
+        let os = OS::ALL.iter().find(|o| o.as_str() == os).unwrap();
+        let minimum = Version::parse(minimum.to_string(), proc_macro2::Span::call_site()).unwrap();
+        let deployment_target = env(os.deployment_target_var())?;
+        if Version::parse(deployment_target, proc_macro2::Span::call_site())? >= minimum {
+            Some(os.as_nv_cfg())
+        } else {
+            None
+        }
+    });
+    quote::quote! {
+        any(#(#cfgs),*)
+    }
+}
+
+/// Availability keys only describe Apple platforms. Neither GNUstep nor the mock
+/// runtime have comparable versioning, so with either enabled every binding is
+/// treated as available on non-Apple targets. Like the rest of the mock
+/// codegen, that follows the consuming crate's `mock` feature.
+fn foreign_runtime_cfg() -> proc_macro2::TokenStream {
+    if cfg!(feature = "gnustep") {
+        quote::quote! {
+            not(target_vendor = "apple")
+        }
+    } else {
+        quote::quote! {
+            all(not(target_vendor = "apple"), feature = "mock")
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
 struct OSVersion {
-    os:      OS,
-    version: Version,
-    span:    proc_macro2::Span,
+    os:          OS,
+    introduced:  Version,
+    deprecated:  Option<Version>,
+    obsoleted:   Option<Version>,
+    /// Declared as `os = "unavailable"`; the version bounds are meaningless.
+    unavailable: bool,
+    span:        proc_macro2::Span,
 }
 
 impl TryFrom<syn::NestedMeta> for OSVersion {
@@ -102,12 +184,35 @@ impl TryFrom<syn::NestedMeta> for OSVersion {
             syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => {
                 let span = nv.span();
                 let os = nv.path.try_into()?;
-                let version = nv.lit.try_into()?;
-                return Ok(Self { os, version, span });
+                let unavailable = match &nv.lit {
+                    syn::Lit::Str(s) => s.value() == "unavailable",
+                    _ => false,
+                };
+                let introduced = if unavailable {
+                    Version::default()
+                } else {
+                    nv.lit.try_into()?
+                };
+                return Ok(Self {
+                    os,
+                    introduced,
+                    deprecated: None,
+                    obsoleted: None,
+                    unavailable,
+                    span,
+                });
+            }
+            syn::NestedMeta::Meta(syn::Meta::List(list)) => {
+                let span = list.span();
+                let os = list.path.try_into()?;
+                return Self::from_bounds(os, list.nested, span);
             }
             _ => {}
         };
-        Err(syn::Error::new(nested_meta.span(), "`OS_NAME = \"#.#.#\"`"))
+        Err(syn::Error::new(
+            nested_meta.span(),
+            "Expected `OS_NAME = \"#.#.#\"`, `OS_NAME = \"unavailable\"` or `OS_NAME(introduced = \"#.#.#\", deprecated = \"#.#.#\", obsoleted = \"#.#.#\")`",
+        ))
     }
 }
 
@@ -118,16 +223,135 @@ impl Spanned for OSVersion {
 }
 
 impl OSVersion {
+    fn from_bounds(
+        os: OS,
+        bounds: Punctuated<syn::NestedMeta, syn::token::Comma>,
+        span: proc_macro2::Span,
+    ) -> parse::Result<Self> {
+        let mut introduced = None;
+        let mut deprecated = None;
+        let mut obsoleted = None;
+        for bound in bounds {
+            let nv = match bound {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => nv,
+                o => {
+                    return Err(syn::Error::new(
+                        o.span(),
+                        "Expected `introduced`, `deprecated` or `obsoleted = \"#.#.#\"`",
+                    ))
+                }
+            };
+            let slot = match nv.path.get_ident() {
+                Some(ident) if ident == "introduced" => &mut introduced,
+                Some(ident) if ident == "deprecated" => &mut deprecated,
+                Some(ident) if ident == "obsoleted" => &mut obsoleted,
+                _ => {
+                    return Err(syn::Error::new(
+                        nv.path.span(),
+                        "Expected one of `introduced`, `deprecated` or `obsoleted`",
+                    ))
+                }
+            };
+            if slot.is_some() {
+                return Err(syn::Error::new(
+                    nv.path.span(),
+                    format!("Duplicate `{}` keys", nv.path.get_ident().unwrap()),
+                ));
+            }
+            let span = nv.span();
+            *slot = Some((nv.lit.try_into()?, span));
+        }
+
+        let introduced = introduced.map(|(version, _)| version).unwrap_or_default();
+        for (bound, name) in [(deprecated, "deprecated"), (obsoleted, "obsoleted")].iter() {
+            if let Some((version, span)) = bound {
+                if *version < introduced {
+                    return Err(syn::Error::new(
+                        *span,
+                        format!("`{}` cannot be older than `introduced`", name),
+                    ));
+                }
+            }
+        }
+        if let (Some((deprecated, _)), Some((obsoleted, span))) = (deprecated, obsoleted) {
+            if obsoleted < deprecated {
+                return Err(syn::Error::new(
+                    span,
+                    "`obsoleted` cannot be older than `deprecated`",
+                ));
+            }
+        }
+        Ok(Self {
+            os,
+            introduced,
+            deprecated: deprecated.map(|(version, _)| version),
+            obsoleted: obsoleted.map(|(version, _)| version),
+            unavailable: false,
+            span,
+        })
+    }
+
+    /// The version `OS_VERSION` reports for `version` on this OS.
+    fn runtime_version(&self, version: Version) -> Version {
+        match self.os {
+            // NSProcessInfo reports the macOS version under Mac Catalyst.
+            OS::macCatalyst => version.catalyst_to_macos(),
+            _ => version,
+        }
+    }
+
+    fn deprecated_attr(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Option<proc_macro2::TokenStream> {
+        let deprecated = self.deprecated?;
+        let deployment_target = env(self.os.deployment_target_var())?;
+        if Version::parse(deployment_target, proc_macro2::Span::call_site())? < deprecated {
+            return None;
+        }
+        let cfg = self.os.as_nv_cfg();
+        let note = format!("Deprecated in `{} {}`", self.os.as_str(), deprecated);
+        Some(quote::quote! {
+            #[cfg_attr(#cfg, deprecated(note = #note))]
+        })
+    }
+
     fn supported_check(&self) -> proc_macro2::TokenStream {
         let cfg = self.os.as_nv_cfg();
-        let Version {
-            major,
-            minor,
-            patch,
-        } = self.version;
+        if self.unavailable {
+            return quote::quote! {
+                if cfg!(#cfg) {
+                    false
+                }
+            };
+        }
+        let introduced = self.runtime_version(self.introduced).atleast_expr();
+        let not_obsoleted = self.obsoleted.map(|obsoleted| {
+            let obsoleted = self.runtime_version(obsoleted).atleast_expr();
+            quote::quote! {
+                && !#obsoleted
+            }
+        });
+        quote::quote! {
+            if cfg!(#cfg) {
+                #introduced #not_obsoleted
+            }
+        }
+    }
+
+    fn availability_check(&self) -> proc_macro2::TokenStream {
+        let cfg = self.os.as_nv_cfg();
+        let required = self.runtime_version(self.introduced).ns_version_expr();
+        let obsoleted = match self.obsoleted {
+            Some(obsoleted) => {
+                let obsoleted = self.runtime_version(obsoleted).ns_version_expr();
+                quote::quote!(Some(#obsoleted))
+            }
+            None => quote::quote!(None),
+        };
         quote::quote! {
             if cfg!(#cfg) {
-                objc_util::os_atleast!(#major, #minor, #patch)
+                objc_util::Unavailable::check(#required, #obsoleted)?;
             }
         }
     }
@@ -135,25 +359,37 @@ impl OSVersion {
     fn debug_assert_stmt(&self, func_name: &syn::Ident) -> proc_macro2::TokenStream {
         let os = self.os.as_str();
         let cfg = self.os.as_nv_cfg();
-        let Version {
-            major,
-            minor,
-            patch,
-        } = self.version;
+        let introduced = self.introduced.to_string();
+        let introduced_check = self.runtime_version(self.introduced).atleast_expr();
+        let obsoleted_assert = self.obsoleted.map(|obsoleted| {
+            let obsoleted_check = self.runtime_version(obsoleted).atleast_expr();
+            let obsoleted = obsoleted.to_string();
+            quote::quote! {
+                debug_assert!(
+                    !#obsoleted_check,
+                    "`{}` was obsoleted in `{} {}` but found `{}.{}.{}`",
+                    stringify!(#func_name),
+                    #os,
+                    #obsoleted,
+                    objc_util::OS_VERSION.major,
+                    objc_util::OS_VERSION.minor,
+                    objc_util::OS_VERSION.patch,
+                );
+            }
+        });
         quote::quote! {
             if cfg!(#cfg) {
                 debug_assert!(
-                    objc_util::os_atleast!(#major, #minor, #patch),
-                    "`{}` requires `{} {}.{}.{}` but found `{}.{}.{}`",
+                    #introduced_check,
+                    "`{}` requires `{} {}` but found `{}.{}.{}`",
                     stringify!(#func_name),
                     #os,
-                    #major,
-                    #minor,
-                    #patch,
+                    #introduced,
                     objc_util::OS_VERSION.major,
                     objc_util::OS_VERSION.minor,
                     objc_util::OS_VERSION.patch,
                 );
+                #obsoleted_assert
             }
         }
     }
@@ -164,6 +400,10 @@ impl OSVersion {
 enum OS {
     iOS,
     macOS,
+    tvOS,
+    watchOS,
+    visionOS,
+    macCatalyst,
 }
 
 impl TryFrom<syn::Path> for OS {
@@ -173,31 +413,129 @@ impl TryFrom<syn::Path> for OS {
         match path.get_ident() {
             Some(ident) if ident == "ios" => Ok(OS::iOS),
             Some(ident) if ident == "macos" => Ok(OS::macOS),
+            Some(ident) if ident == "tvos" => Ok(OS::tvOS),
+            Some(ident) if ident == "watchos" => Ok(OS::watchOS),
+            Some(ident) if ident == "visionos" => Ok(OS::visionOS),
+            Some(ident) if ident == "maccatalyst" => Ok(OS::macCatalyst),
             o => Err(syn::Error::new(
                 o.span(),
-                "Expected one of `ios` or `macos`",
+                "Expected one of `ios`, `macos`, `tvos`, `watchos`, `visionos` or `maccatalyst`",
             )),
         }
     }
 }
 
 impl OS {
+    const ALL: [OS; 6] = [
+        OS::iOS,
+        OS::macOS,
+        OS::tvOS,
+        OS::watchOS,
+        OS::visionOS,
+        OS::macCatalyst,
+    ];
+
     fn as_str(&self) -> &'static str {
         match self {
             OS::iOS => "ios",
             OS::macOS => "macos",
+            OS::tvOS => "tvos",
+            OS::watchOS => "watchos",
+            OS::visionOS => "visionos",
+            OS::macCatalyst => "maccatalyst",
+        }
+    }
+
+    /// The platform's name as written in diagnostics.
+    fn display_name(&self) -> &'static str {
+        match self {
+            OS::iOS => "iOS",
+            OS::macOS => "macOS",
+            OS::tvOS => "tvOS",
+            OS::watchOS => "watchOS",
+            OS::visionOS => "visionOS",
+            OS::macCatalyst => "Mac Catalyst",
+        }
+    }
+
+    /// The deployment target the crate is being built for, as set in the
+    /// environment.
+    fn deployment_target_var(&self) -> &'static str {
+        match self {
+            OS::iOS | OS::macCatalyst => "IPHONEOS_DEPLOYMENT_TARGET",
+            OS::macOS => "MACOSX_DEPLOYMENT_TARGET",
+            OS::tvOS => "TVOS_DEPLOYMENT_TARGET",
+            OS::watchOS => "WATCHOS_DEPLOYMENT_TARGET",
+            OS::visionOS => "XROS_DEPLOYMENT_TARGET",
         }
     }
 
     fn as_nv_cfg(&self) -> proc_macro2::TokenStream {
-        let name = self.as_str();
-        quote::quote! {
-            target_os = #name
+        match self {
+            // Mac Catalyst is an `ios` target, so it's told apart by its ABI.
+            OS::iOS => quote::quote! {
+                all(target_os = "ios", not(target_abi = "macabi"))
+            },
+            OS::macCatalyst => quote::quote! {
+                all(target_os = "ios", target_abi = "macabi")
+            },
+            _ => {
+                let name = self.as_str();
+                quote::quote! {
+                    target_os = #name
+                }
+            }
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// Mac Catalyst releases and the macOS releases that shipped them, from the
+/// `macOS_iOSMac` version map in the macOS SDK's `SDKSettings.json`. Catalyst
+/// started at 13.1, skipped 14.0–14.1, 15.1 and 16.0, and numbers match from
+/// 26 on.
+const CATALYST_TO_MACOS: &[((i64, i64, i64), (i64, i64, i64))] = &[
+    ((13, 1, 0), (10, 15, 0)),
+    ((13, 2, 0), (10, 15, 1)),
+    ((13, 3, 0), (10, 15, 2)),
+    ((13, 3, 1), (10, 15, 3)),
+    ((13, 4, 0), (10, 15, 4)),
+    ((13, 5, 0), (10, 15, 5)),
+    ((13, 6, 0), (10, 15, 6)),
+    ((14, 2, 0), (11, 0, 0)),
+    ((14, 3, 0), (11, 1, 0)),
+    ((14, 4, 0), (11, 2, 0)),
+    ((14, 5, 0), (11, 3, 0)),
+    ((14, 6, 0), (11, 4, 0)),
+    ((14, 7, 0), (11, 5, 0)),
+    ((15, 0, 0), (12, 0, 0)),
+    ((15, 2, 0), (12, 1, 0)),
+    ((15, 3, 0), (12, 2, 0)),
+    ((15, 4, 0), (12, 3, 0)),
+    ((15, 5, 0), (12, 4, 0)),
+    ((15, 6, 0), (12, 5, 0)),
+    ((16, 1, 0), (13, 0, 0)),
+    ((16, 2, 0), (13, 1, 0)),
+    ((16, 3, 0), (13, 2, 0)),
+    ((16, 4, 0), (13, 3, 0)),
+    ((16, 5, 0), (13, 4, 0)),
+    ((16, 6, 0), (13, 5, 0)),
+    ((17, 0, 0), (14, 0, 0)),
+    ((17, 1, 0), (14, 1, 0)),
+    ((17, 2, 0), (14, 2, 0)),
+    ((17, 3, 0), (14, 3, 0)),
+    ((17, 4, 0), (14, 4, 0)),
+    ((17, 5, 0), (14, 5, 0)),
+    ((17, 6, 0), (14, 6, 0)),
+    ((18, 0, 0), (15, 0, 0)),
+    ((18, 1, 0), (15, 1, 0)),
+    ((18, 2, 0), (15, 2, 0)),
+    ((18, 3, 0), (15, 3, 0)),
+    ((18, 4, 0), (15, 4, 0)),
+    ((18, 5, 0), (15, 5, 0)),
+    ((18, 6, 0), (15, 6, 0)),
+];
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 struct Version {
     major: i64,
     minor: i64,
@@ -222,7 +560,70 @@ impl TryFrom<syn::Lit> for Version {
     }
 }
 
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
 impl Version {
+    fn atleast_expr(&self) -> proc_macro2::TokenStream {
+        let Version {
+            major,
+            minor,
+            patch,
+        } = self;
+        quote::quote! {
+            objc_util::os_atleast!(#major, #minor, #patch)
+        }
+    }
+
+    fn ns_version_expr(&self) -> proc_macro2::TokenStream {
+        let Version {
+            major,
+            minor,
+            patch,
+        } = self;
+        quote::quote! {
+            objc_util::NSOperatingSystemVersion {
+                major: #major,
+                minor: #minor,
+                patch: #patch,
+            }
+        }
+    }
+
+    /// The macOS release that first shipped the Mac Catalyst version `self`.
+    /// Catalyst versions follow iOS, and from 26 the numbers match macOS.
+    /// Before that they're looked up in `CATALYST_TO_MACOS`, rounding up to
+    /// the next listed release: a version that never shipped on the Mac is
+    /// only available from the first release past it.
+    fn catalyst_to_macos(self) -> Self {
+        if self.major >= 26 {
+            return self;
+        }
+        CATALYST_TO_MACOS
+            .iter()
+            .map(|&(catalyst, macos)| (Self::from_tuple(catalyst), Self::from_tuple(macos)))
+            .find(|&(catalyst, _)| catalyst >= self)
+            .map_or(
+                Self {
+                    major: 26,
+                    minor: 0,
+                    patch: 0,
+                },
+                |(_, macos)| macos,
+            )
+    }
+
+    fn from_tuple((major, minor, patch): (i64, i64, i64)) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
     fn parse(input: String, span: proc_macro2::Span) -> Option<Self> {
         let version: Punctuated::<_, syn::token::Dot> = input.split('.')
             .map(|elem| syn::LitStr::new(elem, span).parse::<syn::LitInt>())
@@ -250,3 +651,300 @@ impl Version {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `deployment_target_cfg` for the `objc_opt_class` minimums, with the
+    /// `targets` environment.
+    fn opt_class_cfg(targets: &[(&str, &str)]) -> String {
+        let minimums = [("macos", "10.15"), ("ios", "13"), ("maccatalyst", "13")];
+        deployment_target_cfg(&minimums, |var| {
+            targets
+                .iter()
+                .find(|&&(name, _)| name == var)
+                .map(|&(_, target)| target.to_string())
+        })
+        .to_string()
+        .split_whitespace()
+        .collect()
+    }
+
+    #[test]
+    fn deployment_target_cfg_minimums() {
+        assert_eq!(opt_class_cfg(&[]), "any()");
+        assert_eq!(
+            opt_class_cfg(&[("MACOSX_DEPLOYMENT_TARGET", "10.14")]),
+            "any()"
+        );
+        assert_eq!(
+            opt_class_cfg(&[
+                ("MACOSX_DEPLOYMENT_TARGET", "10.15"),
+                ("IPHONEOS_DEPLOYMENT_TARGET", "12.4"),
+            ]),
+            "any(target_os=\"macos\")"
+        );
+        assert_eq!(
+            opt_class_cfg(&[("IPHONEOS_DEPLOYMENT_TARGET", "13")]),
+            "any(all(target_os=\"ios\",not(target_abi=\"macabi\")),\
+             all(target_os=\"ios\",target_abi=\"macabi\"))"
+        );
+    }
+
+    fn version(version: &str) -> Version {
+        Version::parse(version.to_string(), proc_macro2::Span::call_site()).unwrap()
+    }
+
+    fn tokens(tokens: proc_macro2::TokenStream) -> String {
+        tokens.to_string().split_whitespace().collect()
+    }
+
+    #[test]
+    fn catalyst_to_macos() {
+        let versions = [
+            ("13.0", "10.15"),
+            ("13.1", "10.15"),
+            ("13.2", "10.15.1"),
+            ("13.3.1", "10.15.3"),
+            ("13.4", "10.15.4"),
+            ("13.6", "10.15.6"),
+            ("13.7", "11.0"),
+            ("14.0", "11.0"),
+            ("14.2", "11.0"),
+            ("14.5", "11.3"),
+            ("15.1", "12.1"),
+            ("16.0", "13.0"),
+            ("17.4", "14.4"),
+            ("18.6", "15.6"),
+            ("18.7", "26.0"),
+            ("26.0", "26.0"),
+            ("26.1.2", "26.1.2"),
+        ];
+        for &(catalyst, macos) in &versions {
+            assert_eq!(
+                version(catalyst).catalyst_to_macos(),
+                version(macos),
+                "{}",
+                catalyst
+            );
+        }
+    }
+
+    #[test]
+    fn os_cfgs() {
+        let cfgs = [
+            (OS::iOS, "all(target_os=\"ios\",not(target_abi=\"macabi\"))"),
+            (OS::macOS, "target_os=\"macos\""),
+            (OS::tvOS, "target_os=\"tvos\""),
+            (OS::watchOS, "target_os=\"watchos\""),
+            (OS::visionOS, "target_os=\"visionos\""),
+            (
+                OS::macCatalyst,
+                "all(target_os=\"ios\",target_abi=\"macabi\")",
+            ),
+        ];
+        for &(os, cfg) in &cfgs {
+            assert_eq!(tokens(os.as_nv_cfg()), cfg);
+        }
+    }
+
+    #[test]
+    fn deployment_target_cfg_platforms() {
+        let minimums = [
+            ("tvos", "13"),
+            ("watchos", "6"),
+            ("visionos", "1"),
+            ("maccatalyst", "13.1"),
+        ];
+        let cfg = |var: &str, target: &str| {
+            tokens(deployment_target_cfg(&minimums, |name| {
+                Some(target.to_string()).filter(|_| name == var)
+            }))
+        };
+        let platforms = [
+            ("TVOS_DEPLOYMENT_TARGET", "12.4", "any()"),
+            ("TVOS_DEPLOYMENT_TARGET", "13.0", "any(target_os=\"tvos\")"),
+            ("WATCHOS_DEPLOYMENT_TARGET", "5.3", "any()"),
+            (
+                "WATCHOS_DEPLOYMENT_TARGET",
+                "6",
+                "any(target_os=\"watchos\")",
+            ),
+            (
+                "XROS_DEPLOYMENT_TARGET",
+                "1.0",
+                "any(target_os=\"visionos\")",
+            ),
+            ("IPHONEOS_DEPLOYMENT_TARGET", "13.0", "any()"),
+            (
+                "IPHONEOS_DEPLOYMENT_TARGET",
+                "13.1",
+                "any(all(target_os=\"ios\",target_abi=\"macabi\"))",
+            ),
+        ];
+        for &(var, target, expected) in &platforms {
+            assert_eq!(cfg(var, target), expected, "{}={}", var, target);
+        }
+    }
+
+    fn versions(tokens: proc_macro2::TokenStream) -> parse::Result<OSVersions> {
+        use syn::parse::Parser;
+        Punctuated::<syn::NestedMeta, syn::Token![,]>::parse_terminated
+            .parse2(tokens)?
+            .into_iter()
+            .try_into()
+    }
+
+    fn error(tokens: proc_macro2::TokenStream) -> String {
+        match versions(tokens) {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let versions = versions(quote::quote! {
+            macos(introduced = "10.9", deprecated = "10.12", obsoleted = "11"),
+            ios(deprecated = "13.1"),
+        })
+        .unwrap();
+        let macos = versions.versions[&OS::macOS];
+        assert_eq!(macos.introduced, version("10.9"));
+        assert_eq!(macos.deprecated, Some(version("10.12")));
+        assert_eq!(macos.obsoleted, Some(version("11")));
+        let ios = versions.versions[&OS::iOS];
+        assert_eq!(ios.introduced, Version::default());
+        assert_eq!(ios.deprecated, Some(version("13.1")));
+        assert_eq!(ios.obsoleted, None);
+
+        assert_eq!(
+            error(quote::quote!(macos(
+                introduced = "10.9",
+                deprecated = "10.8"
+            ))),
+            "`deprecated` cannot be older than `introduced`"
+        );
+        assert_eq!(
+            error(quote::quote!(macos(
+                introduced = "10.9",
+                obsoleted = "10.8"
+            ))),
+            "`obsoleted` cannot be older than `introduced`"
+        );
+        assert_eq!(
+            error(quote::quote!(macos(deprecated = "11", obsoleted = "10.15"))),
+            "`obsoleted` cannot be older than `deprecated`"
+        );
+        assert_eq!(
+            error(quote::quote!(macos(obsoleted = "11", obsoleted = "12"))),
+            "Duplicate `obsoleted` keys"
+        );
+        assert_eq!(
+            error(quote::quote!(macos(removed = "11"))),
+            "Expected one of `introduced`, `deprecated` or `obsoleted`"
+        );
+    }
+
+    /// Evaluates a `supported_check` expression as if running `os` at `at`.
+    fn eval(expr: &syn::Expr, os: OS, at: Version) -> bool {
+        let block = |block: &syn::Block| match block.stmts.as_slice() {
+            [syn::Stmt::Expr(expr)] => eval(expr, os, at),
+            _ => panic!("unexpected block"),
+        };
+        match expr {
+            syn::Expr::If(expr) => {
+                if eval(&expr.cond, os, at) {
+                    block(&expr.then_branch)
+                } else {
+                    eval(&expr.else_branch.as_ref().unwrap().1, os, at)
+                }
+            }
+            syn::Expr::Block(expr) => block(&expr.block),
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Bool(lit),
+                ..
+            }) => lit.value,
+            syn::Expr::Binary(syn::ExprBinary {
+                left,
+                op: syn::BinOp::And(_),
+                right,
+                ..
+            }) => eval(left, os, at) && eval(right, os, at),
+            syn::Expr::Unary(syn::ExprUnary {
+                op: syn::UnOp::Not(_),
+                expr,
+                ..
+            }) => !eval(expr, os, at),
+            syn::Expr::Macro(expr) => {
+                let name = expr.mac.path.segments.last().unwrap().ident.to_string();
+                match name.as_str() {
+                    "cfg" => tokens(expr.mac.tokens.clone()) == tokens(os.as_nv_cfg()),
+                    "os_atleast" => {
+                        use syn::parse::Parser;
+                        let parts: Vec<i64> =
+                            Punctuated::<syn::LitInt, syn::Token![,]>::parse_terminated
+                                .parse2(expr.mac.tokens.clone())
+                                .unwrap()
+                                .iter()
+                                .map(|part| part.base10_parse().unwrap())
+                                .collect();
+                        at >= Version::from_tuple((parts[0], parts[1], parts[2]))
+                    }
+                    _ => panic!("unexpected macro `{}`", name),
+                }
+            }
+            _ => panic!("unexpected expression"),
+        }
+    }
+
+    #[test]
+    fn supported_check() {
+        let versions = versions(quote::quote! {
+            macos(introduced = "10.9", obsoleted = "11"),
+            ios = "13",
+        })
+        .unwrap();
+        let check = syn::parse2(versions.supported_check()).unwrap();
+        let supported = |os, at| eval(&check, os, version(at));
+        assert!(!supported(OS::macOS, "10.8.5"));
+        assert!(supported(OS::macOS, "10.9"));
+        assert!(supported(OS::macOS, "10.15.7"));
+        assert!(!supported(OS::macOS, "11"));
+        assert!(!supported(OS::macOS, "14.1"));
+        assert!(!supported(OS::iOS, "12.4"));
+        assert!(supported(OS::iOS, "13"));
+        // Checked against the macOS version NSProcessInfo reports.
+        assert!(!supported(OS::macCatalyst, "10.14"));
+        assert!(supported(OS::macCatalyst, "10.15"));
+        assert!(!supported(OS::tvOS, "17"));
+    }
+
+    #[test]
+    fn deprecated_attrs() {
+        let versions = versions(quote::quote! {
+            macos(introduced = "10.9", deprecated = "10.12"),
+            ios(introduced = "8", deprecated = "13"),
+            tvos = "9",
+        })
+        .unwrap();
+        let attrs = |var: &str, target: &str| {
+            tokens(
+                versions.deprecated_attrs(|name| Some(target.to_string()).filter(|_| name == var)),
+            )
+        };
+        assert_eq!(attrs("MACOSX_DEPLOYMENT_TARGET", "10.11"), "");
+        assert_eq!(
+            attrs("MACOSX_DEPLOYMENT_TARGET", "10.12"),
+            "#[cfg_attr(target_os=\"macos\",deprecated(note=\"Deprecatedin`macos10.12.0`\"))]"
+        );
+        assert_eq!(
+            attrs("MACOSX_DEPLOYMENT_TARGET", "12"),
+            "#[cfg_attr(target_os=\"macos\",deprecated(note=\"Deprecatedin`macos10.12.0`\"))]"
+        );
+        assert_eq!(attrs("IPHONEOS_DEPLOYMENT_TARGET", "12.4"), "");
+        assert_eq!(attrs("TVOS_DEPLOYMENT_TARGET", "17"), "");
+        assert_eq!(tokens(versions.deprecated_attrs(|_| None)), "");
+    }
+}
@@ -0,0 +1,199 @@
+//! Ids for the private symbols `compile-time` references export.
+//!
+//! The linker only sees each crate's references by their export names, so
+//! the ids mix in the crate and the invocation's location: two crates, or two
+//! versions of one, never define the same name. Repeated invocations from
+//! one location, as from a `macro_rules!` body, are told apart by counting
+//! them. Everything hashed comes from the source, so expanding a crate twice
+//! gives the same names and the same binary.
+
+use std::{collections::BTreeMap, env, path::Path, sync::Mutex};
+
+static OCCURRENCES: Mutex<Occurrences> = Mutex::new(Occurrences::new());
+
+/// The id of a `kind` reference (e.g. `"sel"`) to `name`, made at `span`.
+pub(crate) fn symbol_id(kind: &str, name: &str, span: proc_macro2::Span) -> String {
+    let key = Key {
+        crate_name: env::var("CARGO_CRATE_NAME").unwrap_or_default(),
+        version: env::var("CARGO_PKG_VERSION").unwrap_or_default(),
+        kind,
+        name,
+        location: location(span),
+    }
+    .to_string();
+    let occurrence = OCCURRENCES.lock().unwrap().next(&key);
+    id(&key, occurrence)
+}
+
+fn location(span: proc_macro2::Span) -> String {
+    let start = span.start();
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    format!(
+        "{}:{}:{}",
+        relative_path(&span.file(), &manifest_dir),
+        start.line,
+        start.column
+    )
+}
+
+/// `file` relative to the crate's directory. Cargo passes registry and git
+/// dependencies by absolute paths, which differ between machines.
+fn relative_path(file: &str, manifest_dir: &str) -> String {
+    match Path::new(file).strip_prefix(manifest_dir) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(_) => file.to_string(),
+    }
+}
+
+struct Key<'a> {
+    crate_name: String,
+    version: String,
+    kind: &'a str,
+    name: &'a str,
+    location: String,
+}
+
+impl std::fmt::Display for Key<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\0{}\0{}\0{}\0{}",
+            self.crate_name, self.version, self.kind, self.name, self.location
+        )
+    }
+}
+
+/// How many references each key has had so far.
+struct Occurrences(BTreeMap<String, usize>);
+
+impl Occurrences {
+    const fn new() -> Self {
+        Occurrences(BTreeMap::new())
+    }
+
+    /// Forgets every reference, as a new compiler process would.
+    #[cfg(test)]
+    fn reset(&mut self) {
+        self.0.clear();
+    }
+
+    fn next(&mut self, key: &str) -> usize {
+        let count = self.0.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        *count - 1
+    }
+}
+
+fn id(key: &str, occurrence: usize) -> String {
+    format!(
+        "{:016x}",
+        fnv1a(format!("{}\0{}", key, occurrence).as_bytes())
+    )
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same on every
+/// compiler.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{objc_selector::ObjCMethName, Lookup};
+    use proc_macro2::TokenTree;
+    use std::convert::TryFrom;
+
+    fn key(version: &str, kind: &str, name: &str, location: &str) -> String {
+        Key {
+            crate_name: "app".to_string(),
+            version: version.to_string(),
+            kind,
+            name,
+            location: location.to_string(),
+        }
+        .to_string()
+    }
+
+    /// The `export_name`s in `tokens`.
+    fn export_names(tokens: proc_macro2::TokenStream) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            match token {
+                TokenTree::Group(group) => names.extend(export_names(group.stream())),
+                TokenTree::Ident(ident) if ident == "export_name" => {
+                    if let Some(TokenTree::Literal(name)) = tokens.nth(1) {
+                        names.push(name.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+        names
+    }
+
+    /// The symbols a fresh compiler emits for the static references in
+    /// `source`: selectors for string literals, classes for identifiers. The
+    /// first reference is made twice from its location, as a `macro_rules!`
+    /// body would.
+    fn expand(source: &proc_macro2::TokenStream) -> Vec<String> {
+        OCCURRENCES.lock().unwrap().reset();
+        let mut tokens = source.clone().into_iter().collect::<Vec<_>>();
+        tokens.insert(0, tokens[0].clone());
+        tokens
+            .into_iter()
+            .flat_map(|token| match token {
+                TokenTree::Literal(lit) => {
+                    let meth_name = ObjCMethName::try_from(syn::Lit::new(lit)).unwrap();
+                    export_names(meth_name.selector_func_body(Lookup::Static))
+                }
+                TokenTree::Ident(class) => {
+                    export_names(crate::class_func_body(&class, Lookup::Static))
+                }
+                _ => panic!("Unexpected token"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn deterministic_across_expansions() {
+        let source = "\"symbolTest\"\n\"symbolTest\" \"symbolTest:\"\nSymbolTest"
+            .parse()
+            .unwrap();
+        let first = expand(&source);
+        assert_eq!(first.len(), 9);
+        assert_eq!(first, expand(&source));
+
+        let mut unique = first.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), first.len());
+    }
+
+    #[test]
+    fn machine_independent_paths() {
+        let manifest_dir = "/home/me/.cargo/registry/src/index/app-1.0.0";
+        assert_eq!(
+            relative_path(&format!("{}/src/lib.rs", manifest_dir), manifest_dir),
+            Path::new("src").join("lib.rs").to_string_lossy()
+        );
+        assert_eq!(relative_path("src/lib.rs", "/work/app"), "src/lib.rs");
+    }
+
+    #[test]
+    fn salted_with_the_version() {
+        assert_ne!(
+            id(&key("1.0.0", "sel", "hash", "src/lib.rs:3:12"), 0),
+            id(&key("1.0.1", "sel", "hash", "src/lib.rs:3:12"), 0)
+        );
+    }
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
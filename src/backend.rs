@@ -0,0 +1,219 @@
+//! Runtime backends.
+//!
+//! On Apple targets we link against Foundation and Apple's libobjc. Everywhere
+//! else (with the `gnustep` feature enabled) we link against GNUstep's
+//! libobjc2 and gnustep-base, which provide the same runtime entry points and
+//! Foundation classes. With only the `mock` feature enabled there is no
+//! Foundation at all, and bindings dispatch through `crate::mock`.
+
+use crate::NSOperatingSystemVersion;
+
+#[cfg(target_vendor = "apple")]
+#[link(name = "Foundation", kind = "framework")]
+extern "C" {}
+
+#[cfg(all(not(target_vendor = "apple"), feature = "gnustep"))]
+#[link(name = "gnustep-base", kind = "dylib")]
+extern "C" {
+    fn NSPageSize() -> crate::NSUInteger;
+}
+
+// Classes are only ever looked up by name, so without a symbol reference
+// `--as-needed` drops gnustep-base and its classes never get registered.
+#[cfg(all(not(target_vendor = "apple"), feature = "gnustep"))]
+#[used]
+static GNUSTEP_BASE_ANCHOR: unsafe extern "C" fn() -> crate::NSUInteger = NSPageSize;
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+#[cold]
+pub(crate) unsafe fn get_os_version() -> NSOperatingSystemVersion {
+    use crate::{
+        dispatch::send,
+        runtime::{Object, BOOL, NO},
+    };
+    use objc::{class, sel, sel_impl};
+    let class = class!(NSProcessInfo) as *const _ as *mut Object;
+    let process_info: *mut Object = send(class, sel!(processInfo), ());
+    // Not every gnustep-base release implements `operatingSystemVersion`.
+    if cfg!(not(target_vendor = "apple")) {
+        let responds: BOOL = send(
+            process_info,
+            sel!(respondsToSelector:),
+            (sel!(operatingSystemVersion),),
+        );
+        if responds == NO {
+            return NSOperatingSystemVersion {
+                major: 0,
+                minor: 0,
+                patch: 0,
+            };
+        }
+    }
+    send(process_info, sel!(operatingSystemVersion), ())
+}
+
+/// There's no OS to ask, and availability checks always pass off Apple
+/// platforms anyway.
+#[cfg(not(any(target_vendor = "apple", feature = "gnustep")))]
+pub(crate) unsafe fn get_os_version() -> NSOperatingSystemVersion {
+    NSOperatingSystemVersion {
+        major: 0,
+        minor: 0,
+        patch: 0,
+    }
+}
+
+/// The function to call, with the receiver and selector as its first two
+/// arguments, for a message returning `R`.
+#[cfg(target_vendor = "apple")]
+pub(crate) unsafe fn imp<R: crate::Encode + 'static>(
+    _receiver: *mut crate::runtime::Object,
+    _sel: crate::runtime::Sel,
+) -> crate::runtime::Imp {
+    use crate::dispatch::MsgSend;
+    extern "C" {
+        fn objc_msgSend();
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        fn objc_msgSend_stret();
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        fn objc_msgSend_fpret();
+    }
+    match MsgSend::of::<R>() {
+        MsgSend::Normal => objc_msgSend,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        MsgSend::Stret => objc_msgSend_stret,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        MsgSend::Fpret => objc_msgSend_fpret,
+        #[allow(unreachable_patterns)]
+        variant => unreachable!("`{:?}` doesn't exist on this architecture", variant),
+    }
+}
+
+/// GNUstep has no portable `objc_msgSend`; its lookup returns the method
+/// implementation (or a nil handler) to call directly, whatever it returns.
+#[cfg(all(not(target_vendor = "apple"), feature = "gnustep"))]
+#[allow(clippy::extra_unused_type_parameters)]
+pub(crate) unsafe fn imp<R>(
+    receiver: *mut crate::runtime::Object,
+    sel: crate::runtime::Sel,
+) -> crate::runtime::Imp {
+    use crate::runtime::{Imp, Object, Sel};
+    extern "C" {
+        fn objc_msg_lookup(receiver: *mut Object, sel: Sel) -> Option<Imp>;
+    }
+    objc_msg_lookup(receiver, sel).expect("objc_msg_lookup returned no implementation")
+}
+
+/// The function to call for a message to `super` returning `R`, and the
+/// receiver to pass it: `sup` itself, for the `objc_msgSendSuper` variants.
+#[cfg(target_vendor = "apple")]
+pub(crate) unsafe fn super_imp<R: crate::Encode + 'static>(
+    sup: &crate::dispatch::ObjCSuper,
+    _sel: crate::runtime::Sel,
+) -> (crate::runtime::Imp, *mut crate::runtime::Object) {
+    use crate::dispatch::MsgSend;
+    extern "C" {
+        fn objc_msgSendSuper();
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        fn objc_msgSendSuper_stret();
+    }
+    let imp: crate::runtime::Imp = match MsgSend::of::<R>() {
+        MsgSend::Normal | MsgSend::Fpret => objc_msgSendSuper,
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_arch = "arm"))]
+        MsgSend::Stret => objc_msgSendSuper_stret,
+        #[allow(unreachable_patterns)]
+        variant => unreachable!("`{:?}` doesn't exist on this architecture", variant),
+    };
+    (imp, sup as *const _ as *mut crate::runtime::Object)
+}
+
+/// The implementation `objc_msg_lookup_super` finds, called with the receiver
+/// itself.
+#[cfg(all(not(target_vendor = "apple"), feature = "gnustep"))]
+#[allow(clippy::extra_unused_type_parameters)]
+pub(crate) unsafe fn super_imp<R>(
+    sup: &crate::dispatch::ObjCSuper,
+    sel: crate::runtime::Sel,
+) -> (crate::runtime::Imp, *mut crate::runtime::Object) {
+    use crate::{
+        dispatch::ObjCSuper,
+        runtime::{Imp, Sel},
+    };
+    extern "C" {
+        fn objc_msg_lookup_super(sup: *const ObjCSuper, sel: Sel) -> Option<Imp>;
+    }
+    let imp = objc_msg_lookup_super(sup, sel)
+        .expect("objc_msg_lookup_super returned no implementation");
+    (imp, sup.receiver)
+}
+
+#[cfg(all(
+    any(target_vendor = "apple", feature = "gnustep"),
+    not(feature = "mock")
+))]
+mod refcount {
+    use crate::runtime::Object;
+
+    extern "C" {
+        pub(crate) fn objc_retain(obj: *mut Object) -> *mut Object;
+        pub(crate) fn objc_release(obj: *mut Object);
+        pub(crate) fn objc_retainAutoreleasedReturnValue(obj: *mut Object) -> *mut Object;
+        pub(crate) fn objc_initWeak(location: *mut *mut Object, obj: *mut Object) -> *mut Object;
+        pub(crate) fn objc_copyWeak(to: *mut *mut Object, from: *mut *mut Object);
+        pub(crate) fn objc_loadWeakRetained(location: *mut *mut Object) -> *mut Object;
+        pub(crate) fn objc_destroyWeak(location: *mut *mut Object);
+    }
+}
+
+#[cfg(all(
+    any(target_vendor = "apple", feature = "gnustep"),
+    not(feature = "mock")
+))]
+pub(crate) use self::refcount::{
+    objc_copyWeak as copy_weak, objc_destroyWeak as destroy_weak, objc_initWeak as init_weak,
+    objc_loadWeakRetained as load_weak_retained, objc_release as release, objc_retain as retain,
+    objc_retainAutoreleasedReturnValue as retain_autoreleased,
+};
+
+/// Mock instances live as long as their retain count says, and weak
+/// references are plain pointers that stop loading once the instance is gone.
+#[cfg(feature = "mock")]
+mod refcount {
+    use crate::{mock, runtime::Object};
+    use std::ptr;
+
+    pub(crate) unsafe fn retain(obj: *mut Object) -> *mut Object {
+        mock::retain(obj)
+    }
+
+    pub(crate) unsafe fn release(obj: *mut Object) {
+        mock::release(obj)
+    }
+
+    pub(crate) unsafe fn retain_autoreleased(obj: *mut Object) -> *mut Object {
+        mock::retain(obj)
+    }
+
+    pub(crate) unsafe fn init_weak(location: *mut *mut Object, obj: *mut Object) -> *mut Object {
+        *location = obj;
+        obj
+    }
+
+    pub(crate) unsafe fn copy_weak(to: *mut *mut Object, from: *mut *mut Object) {
+        *to = *from;
+    }
+
+    pub(crate) unsafe fn load_weak_retained(location: *mut *mut Object) -> *mut Object {
+        match mock::retain_count(*location) {
+            Some(_) => mock::retain(*location),
+            None => ptr::null_mut(),
+        }
+    }
+
+    pub(crate) unsafe fn destroy_weak(location: *mut *mut Object) {
+        *location = ptr::null_mut();
+    }
+}
+
+#[cfg(feature = "mock")]
+pub(crate) use self::refcount::*;
@@ -0,0 +1,282 @@
+//! Objective-C blocks, following the Apple block ABI.
+//!
+//! A block is a struct starting with an `isa` pointer, flags and the function
+//! to invoke, followed by whatever the block captured. [`StackBlock`] wraps a
+//! Rust closure that way. Like a block literal in Objective-C it lives on the
+//! stack, and whoever needs it to outlive the call copies it to the heap with
+//! `_Block_copy`. The copy helper in its descriptor then clones the closure,
+//! and the dispose helper drops it when the heap copy is released. [`RcBlock`]
+//! owns a reference to such a heap block.
+//!
+//! Bindings can take `impl Fn(..)` arguments, which are passed as a
+//! `StackBlock` with the `@?` encoding:
+//!
+//! ```ignore
+//! #[objc(selector = "enumerateObjectsUsingBlock:", macos = "10.6", ios = "4")]
+//! pub fn nsarray_enumerate_objects(
+//!     array: *const runtime::Object,
+//!     block: impl Fn(*const runtime::Object, NSUInteger, *mut runtime::BOOL),
+//! );
+//! ```
+//!
+//! The callee may copy the block and keep it around (as it does with
+//! completion handlers), so these closures must be `'static` and own what
+//! they capture.
+
+use crate::{Encode, Encoding};
+use std::{
+    marker::PhantomData,
+    mem,
+    ops::Deref,
+    os::raw::{c_int, c_ulong, c_void},
+    ptr,
+};
+
+// The closure needs to be cloned and dropped by the runtime.
+const BLOCK_HAS_COPY_DISPOSE: c_int = 1 << 25;
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+extern "C" {
+    static _NSConcreteStackBlock: c_void;
+
+    fn _Block_copy(block: *const c_void) -> *mut c_void;
+    fn _Block_release(block: *const c_void);
+}
+
+/// The mock runtime has no blocks runtime, but blocks can still be invoked.
+fn stack_block_isa() -> *const c_void {
+    #[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+    unsafe {
+        &_NSConcreteStackBlock
+    }
+    #[cfg(not(any(target_vendor = "apple", feature = "gnustep")))]
+    ptr::null()
+}
+
+/// A block taking the arguments `A` (a tuple) and returning `R`.
+///
+/// Only ever used behind a pointer; the captured state of the block follows
+/// this header in memory.
+#[repr(C)]
+pub struct Block<A, R> {
+    isa:      *const c_void,
+    flags:    c_int,
+    reserved: c_int,
+    invoke:   unsafe extern "C" fn(),
+    marker:   PhantomData<fn(A) -> R>,
+}
+
+unsafe impl<A, R> Encode for &Block<A, R> {
+    fn encode() -> Encoding {
+        unsafe { Encoding::from_str("@?") }
+    }
+}
+
+impl<A: BlockArguments, R> Block<A, R> {
+    /// Invokes the block.
+    ///
+    /// # Safety
+    ///
+    /// The block must really take `A` and return `R`.
+    pub unsafe fn call(&self, args: A) -> R {
+        args.call_block(self)
+    }
+}
+
+/// A tuple of the arguments to a block.
+///
+/// # Safety
+///
+/// Implemented for tuples of up to twelve elements; there's no need to
+/// implement it elsewhere.
+pub unsafe trait BlockArguments: Sized {
+    #[doc(hidden)]
+    unsafe fn call_block<R>(self, block: &Block<Self, R>) -> R;
+}
+
+/// A closure that can be invoked as a block taking `A` and returning `R`.
+///
+/// # Safety
+///
+/// Implemented for `Fn` closures taking up to twelve arguments; there's no
+/// need to implement it elsewhere.
+pub unsafe trait IntoBlock<A, R>: Sized {
+    #[doc(hidden)]
+    fn invoke_fn() -> unsafe extern "C" fn();
+}
+
+macro_rules! block_args_impl {
+    ($($a:ident),*) => {
+        unsafe impl<$($a),*> BlockArguments for ($($a,)*) {
+            #[allow(non_snake_case)]
+            unsafe fn call_block<R>(self, block: &Block<Self, R>) -> R {
+                let invoke: unsafe extern "C" fn(&Block<Self, R>, $($a),*) -> R =
+                    mem::transmute(block.invoke);
+                let ($($a,)*) = self;
+                invoke(block, $($a),*)
+            }
+        }
+
+        unsafe impl<$($a,)* R, F> IntoBlock<($($a,)*), R> for F
+        where
+            F: Fn($($a),*) -> R,
+        {
+            fn invoke_fn() -> unsafe extern "C" fn() {
+                #[allow(non_snake_case)]
+                unsafe extern "C" fn invoke<$($a,)* R, F>(
+                    block: &StackBlock<($($a,)*), R, F>,
+                    $($a: $a),*
+                ) -> R
+                where
+                    F: Fn($($a),*) -> R,
+                {
+                    (block.closure)($($a),*)
+                }
+                let invoke: unsafe extern "C" fn(&StackBlock<($($a,)*), R, F>, $($a),*) -> R =
+                    invoke::<$($a,)* R, F>;
+                unsafe { mem::transmute(invoke) }
+            }
+        }
+    };
+}
+
+block_args_impl!();
+block_args_impl!(A);
+block_args_impl!(A, B);
+block_args_impl!(A, B, C);
+block_args_impl!(A, B, C, D);
+block_args_impl!(A, B, C, D, E);
+block_args_impl!(A, B, C, D, E, F0);
+block_args_impl!(A, B, C, D, E, F0, G);
+block_args_impl!(A, B, C, D, E, F0, G, H);
+block_args_impl!(A, B, C, D, E, F0, G, H, I);
+block_args_impl!(A, B, C, D, E, F0, G, H, I, J);
+block_args_impl!(A, B, C, D, E, F0, G, H, I, J, K);
+block_args_impl!(A, B, C, D, E, F0, G, H, I, J, K, L);
+
+#[repr(C)]
+struct BlockDescriptor {
+    reserved: c_ulong,
+    size:     c_ulong,
+    copy:     unsafe extern "C" fn(*mut c_void, *const c_void),
+    dispose:  unsafe extern "C" fn(*mut c_void),
+}
+
+/// A block wrapping the closure `F`, laid out like a block literal.
+#[repr(C)]
+pub struct StackBlock<A, R, F> {
+    isa:        *const c_void,
+    flags:      c_int,
+    reserved:   c_int,
+    invoke:     unsafe extern "C" fn(),
+    descriptor: &'static BlockDescriptor,
+    closure:    F,
+    marker:     PhantomData<fn(A) -> R>,
+}
+
+impl<A, R, F> StackBlock<A, R, F>
+where
+    F: IntoBlock<A, R> + Clone,
+{
+    const DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+        reserved: 0,
+        size:     mem::size_of::<Self>() as c_ulong,
+        copy:     Self::copy_helper,
+        dispose:  Self::dispose_helper,
+    };
+
+    pub fn new(closure: F) -> Self {
+        Self {
+            isa: stack_block_isa(),
+            flags: BLOCK_HAS_COPY_DISPOSE,
+            reserved: 0,
+            invoke: F::invoke_fn(),
+            descriptor: &Self::DESCRIPTOR,
+            closure,
+            marker: PhantomData,
+        }
+    }
+
+    /// Called by the runtime after copying the block's bytes to `dst`. The
+    /// bitwise copy of the closure is overwritten with a proper clone.
+    unsafe extern "C" fn copy_helper(dst: *mut c_void, src: *const c_void) {
+        let src = &*(src as *const Self);
+        let dst = dst as *mut Self;
+        ptr::write(&mut (*dst).closure, src.closure.clone());
+    }
+
+    /// Called by the runtime when the last reference to a copy is released.
+    unsafe extern "C" fn dispose_helper(block: *mut c_void) {
+        ptr::drop_in_place(&mut (*(block as *mut Self)).closure);
+    }
+
+    /// Copies the block to the heap. The copy can outlive `self`, and its type
+    /// no longer names `F`, so the closure can't borrow anything.
+    #[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+    pub fn copy(&self) -> RcBlock<A, R>
+    where
+        F: 'static,
+    {
+        unsafe { RcBlock::copy(&**self) }
+    }
+}
+
+impl<A, R, F> Deref for StackBlock<A, R, F> {
+    type Target = Block<A, R>;
+
+    fn deref(&self) -> &Block<A, R> {
+        unsafe { &*(self as *const Self as *const Block<A, R>) }
+    }
+}
+
+/// A reference to a block on the heap, released on drop.
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub struct RcBlock<A, R> {
+    ptr: ptr::NonNull<Block<A, R>>,
+}
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+impl<A, R> RcBlock<A, R> {
+    /// Wraps `closure` in a block on the heap.
+    pub fn new<F>(closure: F) -> Self
+    where
+        F: IntoBlock<A, R> + Clone + 'static,
+    {
+        StackBlock::new(closure).copy()
+    }
+
+    /// Copies `block` to the heap, or retains it if it's already there.
+    ///
+    /// # Safety
+    ///
+    /// `block` must point to a valid block.
+    pub unsafe fn copy(block: *const Block<A, R>) -> Self {
+        let ptr = _Block_copy(block as *const c_void) as *mut Block<A, R>;
+        Self {
+            ptr: ptr::NonNull::new(ptr).expect("_Block_copy returned null"),
+        }
+    }
+}
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+impl<A, R> Clone for RcBlock<A, R> {
+    fn clone(&self) -> Self {
+        unsafe { Self::copy(self.ptr.as_ptr()) }
+    }
+}
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+impl<A, R> Deref for RcBlock<A, R> {
+    type Target = Block<A, R>;
+
+    fn deref(&self) -> &Block<A, R> {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+impl<A, R> Drop for RcBlock<A, R> {
+    fn drop(&mut self) {
+        unsafe { _Block_release(self.ptr.as_ptr() as *const c_void) }
+    }
+}
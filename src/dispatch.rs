@@ -0,0 +1,289 @@
+//! Choosing the `objc_msgSend` variant for a message.
+//!
+//! Apple's runtime has a variant of `objc_msgSend` for each way the C calling
+//! convention returns values, and the caller has to pick the one matching the
+//! return type:
+//!
+//! * **x86_64**: structs and unions larger than 16 bytes are returned through
+//!   a pointer passed by the caller, and need `objc_msgSend_stret`. `long
+//!   double` is returned on the x87 stack and needs `objc_msgSend_fpret`.
+//!   Everything else, including `NSRange` and `CGPoint`, comes back in
+//!   registers from `objc_msgSend`.
+//! * **x86 (i386)**: structs and unions come back in registers only when they
+//!   are 1, 2, 4 or 8 bytes large; any other size needs `objc_msgSend_stret`.
+//!   `float`, `double` and `long double` are returned on the x87 stack and
+//!   need `objc_msgSend_fpret`.
+//! * **32-bit ARM**: structs and unions larger than 4 bytes need
+//!   `objc_msgSend_stret`. Floating point values come back in core registers.
+//! * **arm64**: the indirect result register is separate from the argument
+//!   registers, so `objc_msgSend` works for every return type. The other
+//!   variants don't exist there.
+//!
+//! The variant only decides how the runtime forwards to the method; the call
+//! itself goes through the variant cast to the method's real signature, so
+//! the compiler passes the hidden struct return pointer where the ABI puts
+//! it.
+//!
+//! Messages to `super` go through `objc_msgSendSuper` and
+//! `objc_msgSendSuper_stret` the same way. They take a pointer to an
+//! [`ObjCSuper`] in place of the receiver, and start the method lookup at its
+//! superclass. There is no `fpret` variant; `objc_msgSend_fpret` only differs
+//! in what it returns for nil receivers, and `super` is never nil.
+//!
+//! GNUstep has none of these variants. Its `objc_msg_lookup` and
+//! `objc_msg_lookup_super` return the method implementation, which is called
+//! directly with the right signature and the receiver itself.
+//!
+//! Messages are normally sent through `extern "C"` function pointers, which
+//! exceptions must not unwind through. `catch_exceptions` bindings use the
+//! `_unwind` functions instead, which call through `extern "C-unwind"` ones so
+//! that an exception can reach the `@catch` around the send.
+
+use crate::{
+    runtime::{Imp, Object, Sel},
+    Encode,
+};
+use std::{any::TypeId, collections::HashMap, mem, sync::RwLock};
+
+/// The architectures Objective-C runs on, as far as dispatch is concerned.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+}
+
+impl Arch {
+    /// The architecture being compiled for.
+    pub fn current() -> Option<Self> {
+        if cfg!(target_arch = "x86") {
+            Some(Arch::X86)
+        } else if cfg!(target_arch = "x86_64") {
+            Some(Arch::X86_64)
+        } else if cfg!(target_arch = "arm") {
+            Some(Arch::Arm)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Arch::Aarch64)
+        } else {
+            None
+        }
+    }
+
+    /// The architecture of a target triple such as `x86_64-apple-ios` or
+    /// `arm64_32-apple-watchos`.
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        let arch = triple.split('-').next()?;
+        match arch {
+            "i386" | "i586" | "i686" => Some(Arch::X86),
+            "x86_64" | "x86_64h" => Some(Arch::X86_64),
+            "aarch64" | "arm64" | "arm64e" | "arm64_32" => Some(Arch::Aarch64),
+            _ if arch.starts_with("arm") || arch.starts_with("thumb") => Some(Arch::Arm),
+            _ => None,
+        }
+    }
+}
+
+/// What kind of value a message returns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReturnKind {
+    /// Integers, pointers, objects, selectors and the like, or nothing.
+    Scalar,
+    /// `float` or `double`.
+    Float,
+    /// `long double`.
+    LongDouble,
+    /// A struct, union or array.
+    Aggregate,
+}
+
+/// The layout of a message's return type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReturnLayout {
+    pub size: usize,
+    pub kind: ReturnKind,
+}
+
+impl ReturnLayout {
+    pub fn of<R: Encode>() -> Self {
+        Self::from_encoding(mem::size_of::<R>(), R::encode().as_str())
+    }
+
+    /// The layout of a type `size` bytes large with the type `encoding`.
+    pub fn from_encoding(size: usize, encoding: &str) -> Self {
+        // Skip type qualifiers such as `r` (`const`).
+        let encoding = encoding.trim_start_matches(|c| "rnNoORV".contains(c));
+        let kind = match encoding.chars().next() {
+            Some('{') | Some('(') | Some('[') => ReturnKind::Aggregate,
+            Some('f') | Some('d') => ReturnKind::Float,
+            Some('D') => ReturnKind::LongDouble,
+            _ => ReturnKind::Scalar,
+        };
+        Self { size, kind }
+    }
+}
+
+/// A variant of `objc_msgSend`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MsgSend {
+    /// `objc_msgSend`
+    Normal,
+    /// `objc_msgSend_stret`
+    Stret,
+    /// `objc_msgSend_fpret`
+    Fpret,
+}
+
+impl MsgSend {
+    /// The variant to send a message returning `ret` with on `arch`.
+    pub fn select(arch: Arch, ret: ReturnLayout) -> Self {
+        match (arch, ret.kind) {
+            (Arch::X86, ReturnKind::Float) | (Arch::X86, ReturnKind::LongDouble) => MsgSend::Fpret,
+            (Arch::X86, ReturnKind::Aggregate) => match ret.size {
+                1 | 2 | 4 | 8 => MsgSend::Normal,
+                _ => MsgSend::Stret,
+            },
+            (Arch::X86_64, ReturnKind::LongDouble) => MsgSend::Fpret,
+            (Arch::X86_64, ReturnKind::Aggregate) if ret.size > 16 => MsgSend::Stret,
+            (Arch::Arm, ReturnKind::Aggregate) if ret.size > 4 => MsgSend::Stret,
+            _ => MsgSend::Normal,
+        }
+    }
+
+    /// The variant to send a message returning `R` with on the current
+    /// architecture. It's selected from `R`'s encoding on the first message
+    /// returning `R`, and cached after that.
+    pub fn of<R: Encode + 'static>() -> Self {
+        lazy_static::lazy_static! {
+            static ref VARIANTS: RwLock<HashMap<TypeId, MsgSend>> = Default::default();
+        }
+        let ret = TypeId::of::<R>();
+        if let Some(&variant) = VARIANTS.read().unwrap().get(&ret) {
+            return variant;
+        }
+        let arch = Arch::current().expect("unsupported architecture");
+        let variant = Self::select(arch, ReturnLayout::of::<R>());
+        VARIANTS.write().unwrap().insert(ret, variant);
+        variant
+    }
+}
+
+/// The `struct objc_super` a message to `super` is sent through.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ObjCSuper {
+    pub receiver:    *mut crate::runtime::Object,
+    /// The class to start the method lookup at.
+    pub super_class: *const crate::runtime::Class,
+}
+
+/// Sends `sel` to `receiver` with `args`, through the variant of
+/// `objc_msgSend` matching `R`.
+#[doc(hidden)]
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub unsafe fn send<A, R>(
+    receiver: *mut crate::runtime::Object,
+    sel: crate::runtime::Sel,
+    args: A,
+) -> R
+where
+    A: objc::MessageArguments,
+    R: Encode + std::any::Any,
+{
+    let imp = crate::backend::imp::<R>(receiver, sel);
+    A::invoke(imp, receiver, sel, args)
+}
+
+/// Sends `sel` to `receiver` with `args`, looking up the method starting at
+/// `superclass`.
+#[doc(hidden)]
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub unsafe fn send_super<A, R>(
+    receiver: *mut crate::runtime::Object,
+    superclass: *const crate::runtime::Class,
+    sel: crate::runtime::Sel,
+    args: A,
+) -> R
+where
+    A: objc::MessageArguments,
+    R: Encode + std::any::Any,
+{
+    let sup = ObjCSuper {
+        receiver,
+        super_class: superclass,
+    };
+    let (imp, receiver) = crate::backend::super_imp::<R>(&sup, sel);
+    A::invoke(imp, receiver, sel, args)
+}
+
+/// Sends `sel` to `receiver` with `args` like [`send`], but through an
+/// `extern "C-unwind"` function pointer, so exceptions may unwind out of it.
+#[doc(hidden)]
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub unsafe fn send_unwind<A, R>(receiver: *mut Object, sel: Sel, args: A) -> R
+where
+    A: UnwindArgs,
+    R: Encode + 'static,
+{
+    let imp = crate::backend::imp::<R>(receiver, sel);
+    A::invoke_unwind(imp, receiver, sel, args)
+}
+
+/// Like [`send_super`], but exceptions may unwind out of it.
+#[doc(hidden)]
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub unsafe fn send_super_unwind<A, R>(
+    receiver: *mut Object,
+    superclass: *const crate::runtime::Class,
+    sel: Sel,
+    args: A,
+) -> R
+where
+    A: UnwindArgs,
+    R: Encode + 'static,
+{
+    let sup = ObjCSuper {
+        receiver,
+        super_class: superclass,
+    };
+    let (imp, receiver) = crate::backend::super_imp::<R>(&sup, sel);
+    A::invoke_unwind(imp, receiver, sel, args)
+}
+
+/// A tuple of message arguments that can be passed through an
+/// `extern "C-unwind"` call.
+#[doc(hidden)]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait UnwindArgs {
+    unsafe fn invoke_unwind<R>(imp: Imp, receiver: *mut Object, sel: Sel, args: Self) -> R;
+}
+
+macro_rules! unwind_args_impls {
+    ($(($($a:ident),*))*) => {$(
+        unsafe impl<$($a),*> UnwindArgs for ($($a,)*) {
+            #[allow(non_snake_case)]
+            unsafe fn invoke_unwind<R>(imp: Imp, receiver: *mut Object, sel: Sel, args: Self) -> R {
+                let ($($a,)*) = args;
+                let imp: unsafe extern "C-unwind" fn(*mut Object, Sel $(, $a)*) -> R =
+                    mem::transmute(imp);
+                imp(receiver, sel $(, $a)*)
+            }
+        }
+    )*};
+}
+
+unwind_args_impls! {
+    ()
+    (A0)
+    (A0, A1)
+    (A0, A1, A2)
+    (A0, A1, A2, A3)
+    (A0, A1, A2, A3, A4)
+    (A0, A1, A2, A3, A4, A5)
+    (A0, A1, A2, A3, A4, A5, A6)
+    (A0, A1, A2, A3, A4, A5, A6, A7)
+    (A0, A1, A2, A3, A4, A5, A6, A7, A8)
+    (A0, A1, A2, A3, A4, A5, A6, A7, A8, A9)
+    (A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10)
+    (A0, A1, A2, A3, A4, A5, A6, A7, A8, A9, A10, A11)
+}
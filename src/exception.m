@@ -0,0 +1,18 @@
+// The `@try`/`@catch` trampoline behind `objc_util::exception::catch`.
+//
+// Only Objective-C exceptions are caught. Rust panics unwind through the
+// `@try` untouched.
+
+#include <objc/objc.h>
+
+id objc_retain(id value);
+
+int objc_util_try(void (*f)(void *), void *context, id *exception) {
+    @try {
+        f(context);
+        return 0;
+    } @catch (id thrown) {
+        *exception = objc_retain(thrown);
+        return 1;
+    }
+}
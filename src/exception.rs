@@ -0,0 +1,135 @@
+//! Catching Objective-C exceptions.
+//!
+//! An `NSException` unwinding through Rust frames is undefined behavior, so
+//! messages that may throw have to be sent inside [`catch`]. Bindings flagged
+//! `catch_exceptions` do that themselves and return the exception as an
+//! error:
+//!
+//! ```ignore
+//! #[objc(selector = "objectAtIndex:", catch_exceptions, macos = "10", ios = "2")]
+//! pub fn nsarray_object_at_index(
+//!     array: *const NSArray,
+//!     index: NSUInteger,
+//! ) -> Result<*mut runtime::Object, Id<NSException>>;
+//! ```
+//!
+//! The `exception` feature compiles the `@try`/`@catch` trampoline this
+//! needs with `cc`. For GNUstep that has to be clang, the only compiler
+//! targeting its 2.0 runtime, so set `CC=clang` where `cc` is gcc. The mock
+//! runtime has no exceptions of its own; method implementations throw with
+//! `mock::throw` instead.
+
+use crate::{runtime::Object, Id, Message};
+
+/// An `NSException` instance.
+#[repr(C)]
+pub struct NSException {
+    _private: [u8; 0],
+}
+
+unsafe impl Message for NSException {}
+
+impl NSException {
+    /// The `NSString` naming the kind of exception, e.g.
+    /// `NSInvalidArgumentException`.
+    pub fn name(&self) -> Option<Id<Object>> {
+        unsafe { self.get("name") }
+    }
+
+    /// The `NSString` describing what went wrong.
+    pub fn reason(&self) -> Option<Id<Object>> {
+        unsafe { self.get("reason") }
+    }
+
+    /// The `NSDictionary` of further information, if any.
+    pub fn user_info(&self) -> Option<Id<Object>> {
+        unsafe { self.get("userInfo") }
+    }
+
+    #[cfg(not(feature = "mock"))]
+    unsafe fn get(&self, selector: &str) -> Option<Id<Object>> {
+        let receiver = self as *const Self as *mut Object;
+        let sel = crate::runtime::Sel::register(selector);
+        Id::from_autoreleased_ptr(crate::dispatch::send(receiver, sel, ()))
+    }
+
+    #[cfg(feature = "mock")]
+    unsafe fn get(&self, selector: &str) -> Option<Id<Object>> {
+        match crate::mock::send_message(self, crate::mock::sel(selector), ()) {
+            Ok(ptr) => Id::from_autoreleased_ptr(ptr),
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// Calls `closure`, returning the Objective-C exception it throws, if any.
+/// Panics pass through unaffected.
+///
+/// # Safety
+///
+/// The Objective-C code `closure` calls must be exception safe, which Cocoa
+/// mostly isn't: objects an exception unwinds through may be left
+/// inconsistent. Rust frames between the `@throw` and `catch` are unwound
+/// like a panic unwinds them, with their destructors run, so they must leave
+/// things consistent when interrupted at any message send. The exception
+/// also has to leave Objective-C through an `extern "C-unwind"` call;
+/// unwinding out of an `extern "C"` one, as the messages of bindings without
+/// `catch_exceptions` are sent, is undefined behavior.
+#[cfg(not(feature = "mock"))]
+pub unsafe fn catch<F, R>(closure: F) -> Result<R, Id<NSException>>
+where
+    F: FnOnce() -> R,
+{
+    use std::{
+        os::raw::{c_int, c_void},
+        ptr,
+    };
+
+    extern "C-unwind" {
+        fn objc_util_try(
+            f: unsafe extern "C-unwind" fn(*mut c_void),
+            context: *mut c_void,
+            exception: *mut *mut Object,
+        ) -> c_int;
+    }
+
+    unsafe extern "C-unwind" fn call<F, R>(context: *mut c_void)
+    where
+        F: FnOnce() -> R,
+    {
+        let (closure, result) = &mut *(context as *mut (Option<F>, Option<R>));
+        *result = Some(closure.take().unwrap()());
+    }
+
+    let mut context: (Option<F>, Option<R>) = (Some(closure), None);
+    let mut exception = ptr::null_mut();
+    let context_ptr = &mut context as *mut _ as *mut c_void;
+    if objc_util_try(call::<F, R>, context_ptr, &mut exception) == 0 {
+        Ok(context.1.unwrap())
+    } else {
+        Err(Id::from_retained_ptr(exception as *mut NSException).expect("nil was thrown"))
+    }
+}
+
+/// Calls `closure`, returning the exception a mock method implementation
+/// throws with `mock::throw`, if any. Panics pass through unaffected.
+///
+/// # Safety
+///
+/// Always safe with the mock runtime; `unsafe` to match the real one.
+#[cfg(feature = "mock")]
+pub unsafe fn catch<F, R>(closure: F) -> Result<R, Id<NSException>>
+where
+    F: FnOnce() -> R,
+{
+    use std::panic;
+
+    match panic::catch_unwind(panic::AssertUnwindSafe(closure)) {
+        Ok(result) => Ok(result),
+        Err(payload) => match payload.downcast::<crate::mock::Thrown>() {
+            Ok(thrown) => Err(Id::from_retained_ptr(thrown.0 as *mut NSException)
+                .expect("nil was thrown")),
+            Err(payload) => panic::resume_unwind(payload),
+        },
+    }
+}
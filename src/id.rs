@@ -0,0 +1,176 @@
+//! Owned references to Objective-C objects.
+//!
+//! An [`Id`] owns one strong reference: cloning it retains the object and
+//! dropping it releases it. A [`WeakId`] is a zeroing weak reference that
+//! loads as `None` once the object is deallocated.
+//!
+//! Bindings can return either directly, following the Cocoa ownership rules.
+//! Methods in the `alloc`, `new`, `copy`, `mutableCopy` and `init` families
+//! hand over a reference the `Id` takes; any other method's result is
+//! autoreleased and gets retained. A family name has to end at a camelCase
+//! word boundary, so `copyItem` is a `copy` method but `copyright` isn't.
+//! `returns_retained` and `returns_autoreleased` override the family for
+//! methods that don't follow the conventions. `init` methods also take over
+//! their receiver, which they can be given as an `Id`:
+//!
+//! ```ignore
+//! #[objc(class = "NSData", selector = "new", macos = "10", ios = "2")]
+//! pub fn nsdata_new() -> Id<NSData>;
+//!
+//! #[objc(selector = "initWithCapacity:", macos = "10", ios = "2")]
+//! pub fn nsmutable_array_init_with_capacity(
+//!     array: Id<NSMutableArray>,
+//!     capacity: NSUInteger,
+//! ) -> Option<Id<NSMutableArray>>;
+//!
+//! #[objc(selector = "firstObject", macos = "10.6", ios = "4")]
+//! pub fn nsarray_first_object(array: *const runtime::Object) -> Option<Id<runtime::Object>>;
+//! ```
+
+use crate::{backend, runtime::Object, Message};
+use std::{cell::UnsafeCell, fmt, marker::PhantomData, ops::Deref, ptr::NonNull};
+
+/// A strong reference to an object. Laid out like a pointer, with `None` as
+/// nil in an `Option<Id<T>>`.
+#[repr(transparent)]
+pub struct Id<T: Message> {
+    ptr:    NonNull<T>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Message> Id<T> {
+    /// Takes over the +1 reference `ptr` points to, as returned by `alloc`,
+    /// `new`, `copy`, `mutableCopy` and `init` methods.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be nil or an object the caller owns a reference to.
+    pub unsafe fn from_retained_ptr(ptr: *mut T) -> Option<Self> {
+        NonNull::new(ptr).map(|ptr| Self {
+            ptr,
+            marker: PhantomData,
+        })
+    }
+
+    /// Retains `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be nil or point to a valid object.
+    pub unsafe fn from_ptr(ptr: *mut T) -> Option<Self> {
+        Self::from_retained_ptr(backend::retain(ptr as *mut Object) as *mut T)
+    }
+
+    /// Retains the autoreleased `ptr` returned by a method, which lets the
+    /// runtime skip the autorelease where it can.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be nil or point to a valid object.
+    pub unsafe fn from_autoreleased_ptr(ptr: *mut T) -> Option<Self> {
+        Self::from_retained_ptr(backend::retain_autoreleased(ptr as *mut Object) as *mut T)
+    }
+
+    pub fn as_ptr(this: &Self) -> *mut T {
+        this.ptr.as_ptr()
+    }
+
+    /// Gives up the reference without releasing it.
+    pub fn into_raw(this: Self) -> *mut T {
+        let ptr = Self::as_ptr(&this);
+        std::mem::forget(this);
+        ptr
+    }
+}
+
+impl<T: Message> Clone for Id<T> {
+    fn clone(&self) -> Self {
+        unsafe {
+            backend::retain(self.ptr.as_ptr() as *mut Object);
+        }
+        Self {
+            ptr:    self.ptr,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Message> Drop for Id<T> {
+    fn drop(&mut self) {
+        unsafe { backend::release(self.ptr.as_ptr() as *mut Object) }
+    }
+}
+
+impl<T: Message> Deref for Id<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: Message> fmt::Pointer for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Pointer::fmt(&self.ptr, f)
+    }
+}
+
+impl<T: Message> fmt::Debug for Id<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Id").field(&self.ptr).finish()
+    }
+}
+
+/// A zeroing weak reference to an object.
+pub struct WeakId<T: Message> {
+    // The runtime keeps track of where weak references live, so the
+    // location must not move.
+    location: Box<UnsafeCell<*mut Object>>,
+    marker:   PhantomData<T>,
+}
+
+impl<T: Message> WeakId<T> {
+    pub fn new(obj: &Id<T>) -> Self {
+        let location = Box::new(UnsafeCell::new(std::ptr::null_mut()));
+        unsafe {
+            backend::init_weak(location.get(), Id::as_ptr(obj) as *mut Object);
+        }
+        Self {
+            location,
+            marker: PhantomData,
+        }
+    }
+
+    /// A strong reference to the object, unless it was deallocated.
+    pub fn load(&self) -> Option<Id<T>> {
+        unsafe {
+            let ptr = backend::load_weak_retained(self.location.get());
+            Id::from_retained_ptr(ptr as *mut T)
+        }
+    }
+}
+
+impl<T: Message> Clone for WeakId<T> {
+    fn clone(&self) -> Self {
+        let location = Box::new(UnsafeCell::new(std::ptr::null_mut()));
+        unsafe {
+            backend::copy_weak(location.get(), self.location.get());
+        }
+        Self {
+            location,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Message> Drop for WeakId<T> {
+    fn drop(&mut self) {
+        unsafe { backend::destroy_weak(self.location.get()) }
+    }
+}
+
+impl<T: Message> fmt::Debug for WeakId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WeakId").finish()
+    }
+}
@@ -1,24 +1,43 @@
-#![cfg(any(target_os = "macos", target_os = "ios"))]
+#![cfg(any(target_vendor = "apple", feature = "gnustep", feature = "mock"))]
 
+mod backend;
+pub mod block;
+pub mod dispatch;
+#[cfg(any(feature = "exception", feature = "mock"))]
+pub mod exception;
+mod id;
 mod macros;
+#[cfg(feature = "mock")]
+pub mod mock;
+mod nserror;
+#[doc(hidden)]
+pub mod varargs;
 
 #[doc(hidden)]
 pub use objc;
+pub use id::{Id, WeakId};
+#[doc(hidden)]
+pub use nserror::ErrorOut;
+pub use nserror::NSError;
 pub use objc::{runtime, Encode, Encoding, Message};
+pub use varargs::{VarArg, VarArgs};
 #[doc(hidden)]
-pub use objc_macros::{class_impl, extern_objc, os_supports_impl, sel_impl};
+pub use objc_macros::{class_impl, class_opt_impl, extern_objc, os_supports_impl, sel_impl};
+
+use std::{error, fmt};
 
 pub type NSUInteger = std::os::raw::c_ulong;
 pub type NSInteger = std::os::raw::c_long;
 
-#[cfg(feature = "compile-time")]
+// Bindings can ask for static lookup without the `compile-time` feature.
+#[cfg(target_vendor = "apple")]
 #[link_section = "__DATA,__objc_imageinfo,regular,no_dead_strip"]
 #[export_name = "\x01L_OBJC_IMAGE_INFO"]
 #[used]
 static IMAGE_INFO: [u32; 2] = [0, 64];
 
-#[link(name = "Foundation", kind = "framework")]
-extern "C" {}
+#[cfg(all(feature = "compile-time", not(target_vendor = "apple")))]
+compile_error!("The `compile-time` feature emits Mach-O sections and requires an Apple target");
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -44,13 +63,59 @@ impl NSOperatingSystemVersion {
     }
 }
 
-#[cold]
-unsafe fn get_os_version() -> NSOperatingSystemVersion {
-    use objc::{class, msg_send, sel, sel_impl};
-    let process_info: *mut runtime::Object = msg_send![class!(NSProcessInfo), processInfo];
-    msg_send![process_info, operatingSystemVersion]
+impl fmt::Display for NSOperatingSystemVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
 }
 
+/// Returned by the `try_` wrapper of a binding when the running OS doesn't
+/// provide the method.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Unavailable {
+    /// The first version providing the method.
+    pub required:  NSOperatingSystemVersion,
+    /// The version the method was removed in, if that's why it's unavailable.
+    pub obsoleted: Option<NSOperatingSystemVersion>,
+    /// The version we are running on.
+    pub found:     NSOperatingSystemVersion,
+}
+
+impl Unavailable {
+    #[doc(hidden)]
+    pub fn check(
+        required: NSOperatingSystemVersion,
+        obsoleted: Option<NSOperatingSystemVersion>,
+    ) -> Result<(), Self> {
+        let found = *OS_VERSION;
+        let obsoleted = obsoleted.filter(|obsoleted| found >= *obsoleted);
+        if found < required || obsoleted.is_some() {
+            Err(Self {
+                required,
+                obsoleted,
+                found,
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.obsoleted {
+            Some(obsoleted) => write!(
+                f,
+                "obsoleted in `{}` but found `{}`",
+                obsoleted, self.found
+            ),
+            None => write!(f, "requires `{}` but found `{}`", self.required, self.found),
+        }
+    }
+}
+
+impl error::Error for Unavailable {}
+
 lazy_static::lazy_static! {
-    pub static ref OS_VERSION: NSOperatingSystemVersion = unsafe { get_os_version() };
+    pub static ref OS_VERSION: NSOperatingSystemVersion = unsafe { backend::get_os_version() };
 }
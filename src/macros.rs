@@ -22,6 +22,15 @@ macro_rules! sel {
     }};
 }
 
+/// The class named by the argument.
+///
+/// `class!(NSData)` evaluates to a `&'static Class` and panics if the class
+/// doesn't exist. It's looked up by name unless the `compile-time` feature is
+/// on; `runtime NSData` and `static NSData` pick the lookup explicitly.
+///
+/// With availability keys, `class!(NSData, ios = "14")` evaluates to an
+/// `Option<&'static Class>` instead, `None` when the running OS predates the
+/// class or it isn't there. That form is looked up like `class_opt!`.
 #[macro_export(local_inner_macros)]
 macro_rules! class {
     ($($t:tt)*) => {{
@@ -33,6 +42,25 @@ macro_rules! class {
     }};
 }
 
+/// The class named by the argument as an `Option<&'static Class>`, `None`
+/// when it doesn't exist. Availability keys are accepted as with `class!`.
+///
+/// Optional classes are always looked up by name with `Class::get`, even
+/// under the `compile-time` feature, and `static` is rejected. A static
+/// reference to a missing class fails the whole binary at load time, and the
+/// weak imports clang uses for `__attribute__((weak_import))` classes need
+/// `#[linkage = "extern_weak"]`, which is unstable.
+#[macro_export(local_inner_macros)]
+macro_rules! class_opt {
+    ($($t:tt)*) => {{
+        struct _Dummy;
+        impl _Dummy {
+            $crate::class_opt_impl!($($t)*);
+        }
+        _Dummy::proc_macro_support_wrapper()
+    }};
+}
+
 #[macro_export(local_inner_macros)]
 macro_rules! os_supports {
     ($($t:tt)*) => {{
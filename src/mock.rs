@@ -0,0 +1,453 @@
+//! An in-process stand-in for the Objective-C runtime.
+//!
+//! The wrappers generated by `extern_objc` (along with `sel!` and `class!`)
+//! dispatch through this module instead of the real runtime in crates whose
+//! own `mock` feature is enabled. Every crate with bindings should declare
+//! the feature, forwarding it with `mock = ["objc-util/mock"]`. Classes, instances and method implementations are
+//! registered from Rust, and every message sent through a binding is recorded
+//! so tests can assert on the receiver, selector and arguments.
+//!
+//! Method implementations must use exactly the argument and return types of
+//! the binding that calls them. The receiver is passed first, followed by the
+//! remaining arguments as a tuple:
+//!
+//! ```ignore
+//! let nsdata = mock::register_class("NSData");
+//! mock::add_method(nsdata, "hash", |_obj, (): ()| 42 as NSUInteger);
+//! let obj = mock::alloc(nsdata);
+//! assert_eq!(unsafe { nsobj_hash(obj) }, 42);
+//! assert_eq!(mock::messages()[0].selector(), "hash");
+//! ```
+//!
+//! Instances are reference counted like real objects: `alloc` returns one
+//! reference, `Id` retains and releases them, and an instance whose count
+//! drops to zero is deallocated. The mock runtime never autoreleases, so
+//! bindings returning an `Id` from methods outside the `alloc`, `new`, `copy`,
+//! `mutableCopy` and `init` families add a reference the test still owns.
+//!
+//! Implementations throw exceptions with `throw`, which unwinds as a panic
+//! that `exception::catch` turns back into the exception.
+//!
+//! Registered classes, instances and recorded messages are per thread, so
+//! tests running in parallel don't observe each other. Off Apple targets, the
+//! build script links an empty stand-in for the `libobjc` that the `objc`
+//! crate requires, so mock builds don't need a runtime installed.
+
+use crate::runtime::{Class, Object, Sel};
+use std::{
+    any::{self, Any},
+    cell::RefCell,
+    collections::HashMap,
+    error,
+    ffi::{CStr, CString},
+    fmt, mem,
+    rc::Rc,
+    sync::Mutex,
+};
+
+type Imp<A, R> = Box<dyn Fn(*mut Object, A) -> R>;
+
+lazy_static::lazy_static! {
+    // Selectors are interned process-wide so `Sel`s compare equal across threads.
+    static ref SELECTORS: Mutex<HashMap<String, usize>> = Default::default();
+}
+
+thread_local! {
+    static RUNTIME: RefCell<Runtime> = Default::default();
+}
+
+#[derive(Default)]
+struct Runtime {
+    class_names: HashMap<String, usize>,
+    classes:     HashMap<usize, ClassData>,
+    instances:   HashMap<usize, usize>,
+    /// The retain count of each live instance.
+    retains:     HashMap<usize, usize>,
+    messages:    Vec<SentMessage>,
+}
+
+struct ClassData {
+    name:             String,
+    superclass:       Option<usize>,
+    instance_methods: HashMap<usize, Rc<dyn Any>>,
+    class_methods:    HashMap<usize, Rc<dyn Any>>,
+}
+
+impl Runtime {
+    fn lookup(&self, receiver: usize, sel: Sel) -> Result<Rc<dyn Any>, Error> {
+        let (class, is_class) = self.receiver_class(receiver, sel)?;
+        self.lookup_in(class, is_class, sel)
+    }
+
+    /// The class of `receiver`, and whether `receiver` is that class itself.
+    fn receiver_class(&self, receiver: usize, sel: Sel) -> Result<(usize, bool), Error> {
+        if self.classes.contains_key(&receiver) {
+            Ok((receiver, true))
+        } else if let Some(&class) = self.instances.get(&receiver) {
+            Ok((class, false))
+        } else {
+            Err(Error::UnknownReceiver {
+                receiver: receiver as *mut Object,
+                selector: sel_name(sel).to_owned(),
+            })
+        }
+    }
+
+    /// Looks up a class or instance method, starting at `class`.
+    fn lookup_in(&self, class: usize, is_class: bool, sel: Sel) -> Result<Rc<dyn Any>, Error> {
+        let mut current = Some(class);
+        while let Some(data) = current.map(|class| &self.classes[&class]) {
+            let methods = if is_class {
+                &data.class_methods
+            } else {
+                &data.instance_methods
+            };
+            if let Some(imp) = methods.get(&(sel.as_ptr() as usize)) {
+                return Ok(Rc::clone(imp));
+            }
+            current = data.superclass;
+        }
+        Err(Error::UnrecognizedSelector {
+            class:    self.classes[&class].name.clone(),
+            selector: sel_name(sel).to_owned(),
+            is_class,
+        })
+    }
+
+    fn class_data(&mut self, class: &Class) -> &mut ClassData {
+        self.classes
+            .get_mut(&(class as *const Class as usize))
+            .expect("Class was not registered with `objc_util::mock`")
+    }
+}
+
+fn with_runtime<T>(f: impl FnOnce(&mut Runtime) -> T) -> T {
+    RUNTIME.with(|runtime| f(&mut runtime.borrow_mut()))
+}
+
+fn sel_name(sel: Sel) -> &'static str {
+    unsafe { CStr::from_ptr(sel.as_ptr() as *const _) }
+        .to_str()
+        .unwrap()
+}
+
+/// Every address handed out by the mock runtime is a unique leaked allocation.
+fn new_address() -> usize {
+    Box::into_raw(Box::new(0usize)) as usize
+}
+
+/// Registers (or returns the already registered) selector named `name`.
+pub fn sel(name: &str) -> Sel {
+    let mut selectors = SELECTORS.lock().unwrap();
+    let ptr = *selectors.entry(name.to_owned()).or_insert_with(|| {
+        CString::new(name)
+            .expect("Selector names cannot contain NUL bytes")
+            .into_raw() as usize
+    });
+    unsafe { Sel::from_ptr(ptr as *const _) }
+}
+
+/// Looks up a class registered on this thread, panicking if there is none.
+pub fn class(name: &str) -> &'static Class {
+    match class_opt(name) {
+        Some(class) => class,
+        None => panic!("Class with name {} could not be found", name),
+    }
+}
+
+/// Looks up a class registered on this thread.
+pub fn class_opt(name: &str) -> Option<&'static Class> {
+    with_runtime(|runtime| runtime.class_names.get(name).copied())
+        .map(|class| unsafe { &*(class as *const Class) })
+}
+
+/// Registers a root class named `name`, or returns it if it already exists.
+pub fn register_class(name: &str) -> &'static Class {
+    register(name, None)
+}
+
+/// Registers a class named `name` inheriting the methods of `superclass`, or
+/// returns it if it already exists.
+pub fn register_subclass(name: &str, superclass: &Class) -> &'static Class {
+    register(name, Some(superclass as *const Class as usize))
+}
+
+fn register(name: &str, superclass: Option<usize>) -> &'static Class {
+    let class = with_runtime(|runtime| {
+        if let Some(&class) = runtime.class_names.get(name) {
+            return class;
+        }
+        let class = new_address();
+        runtime.class_names.insert(name.to_owned(), class);
+        runtime.classes.insert(
+            class,
+            ClassData {
+                name: name.to_owned(),
+                superclass,
+                instance_methods: Default::default(),
+                class_methods: Default::default(),
+            },
+        );
+        class
+    });
+    unsafe { &*(class as *const Class) }
+}
+
+/// Creates a new instance of `class`.
+pub fn alloc(class: &Class) -> *mut Object {
+    with_runtime(|runtime| {
+        runtime.class_data(class);
+        let obj = new_address();
+        runtime.instances.insert(obj, class as *const Class as usize);
+        runtime.retains.insert(obj, 1);
+        obj as *mut Object
+    })
+}
+
+/// The retain count of `obj`, or `None` if it was deallocated (or isn't an
+/// instance).
+pub fn retain_count(obj: *const Object) -> Option<usize> {
+    with_runtime(|runtime| runtime.retains.get(&(obj as usize)).copied())
+}
+
+/// Bumps the retain count of `obj`, for implementations of `retain` and the
+/// like. Classes aren't reference counted.
+pub fn retain(obj: *mut Object) -> *mut Object {
+    with_runtime(|runtime| {
+        if let Some(count) = runtime.retains.get_mut(&(obj as usize)) {
+            *count += 1;
+        }
+    });
+    obj
+}
+
+/// Deallocates `obj` once its retain count reaches zero. Sending it messages
+/// afterwards fails like messaging an unknown receiver.
+pub(crate) fn release(obj: *mut Object) {
+    with_runtime(|runtime| {
+        let obj = obj as usize;
+        if let Some(count) = runtime.retains.get_mut(&obj) {
+            *count -= 1;
+            if *count == 0 {
+                runtime.retains.remove(&obj);
+                runtime.instances.remove(&obj);
+            }
+        }
+    })
+}
+
+/// Implements the instance method `selector` on `class`, replacing any
+/// previous implementation.
+pub fn add_method<A, R, F>(class: &Class, selector: &str, imp: F)
+where
+    A: 'static,
+    R: 'static,
+    F: Fn(*mut Object, A) -> R + 'static,
+{
+    let sel = sel(selector).as_ptr() as usize;
+    let imp: Rc<dyn Any> = Rc::new(Box::new(imp) as Imp<A, R>);
+    with_runtime(|runtime| runtime.class_data(class).instance_methods.insert(sel, imp));
+}
+
+/// Implements the class method `selector` on `class`, replacing any previous
+/// implementation. The class itself is passed as the receiver.
+pub fn add_class_method<A, R, F>(class: &Class, selector: &str, imp: F)
+where
+    A: 'static,
+    R: 'static,
+    F: Fn(*mut Object, A) -> R + 'static,
+{
+    let sel = sel(selector).as_ptr() as usize;
+    let imp: Rc<dyn Any> = Rc::new(Box::new(imp) as Imp<A, R>);
+    with_runtime(|runtime| runtime.class_data(class).class_methods.insert(sel, imp));
+}
+
+/// All messages sent on this thread since the last `reset`, oldest first.
+pub fn messages() -> Vec<SentMessage> {
+    with_runtime(|runtime| runtime.messages.clone())
+}
+
+/// Forgets every class, instance and message registered on this thread.
+pub fn reset() {
+    with_runtime(|runtime| *runtime = Default::default())
+}
+
+/// A message recorded by the mock runtime.
+#[derive(Clone)]
+pub struct SentMessage {
+    receiver: *mut Object,
+    selector: &'static str,
+    args:     Rc<dyn Any>,
+}
+
+impl SentMessage {
+    pub fn receiver(&self) -> *mut Object {
+        self.receiver
+    }
+
+    pub fn selector(&self) -> &'static str {
+        self.selector
+    }
+
+    /// The message arguments (without the receiver), if they are of type `A`.
+    pub fn args<A: 'static>(&self) -> Option<&A> {
+        self.args.downcast_ref()
+    }
+}
+
+impl fmt::Debug for SentMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SentMessage")
+            .field("receiver", &self.receiver)
+            .field("selector", &self.selector)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownReceiver {
+        receiver: *mut Object,
+        selector: String,
+    },
+    UnrecognizedSelector {
+        class:    String,
+        selector: String,
+        is_class: bool,
+    },
+    SignatureMismatch {
+        selector: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownReceiver { receiver, selector } => write!(
+                f,
+                "`{}` sent to {:?}, which was not created by the mock runtime",
+                selector, receiver
+            ),
+            Error::UnrecognizedSelector {
+                class,
+                selector,
+                is_class,
+            } => write!(
+                f,
+                "unrecognized selector {}[{} {}]",
+                if *is_class { '+' } else { '-' },
+                class,
+                selector
+            ),
+            Error::SignatureMismatch { selector, expected } => write!(
+                f,
+                "`{}` is not implemented as `{}`",
+                selector, expected
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+fn downcast<A: 'static, R: 'static>(imp: &Rc<dyn Any>, sel: Sel) -> Result<&Imp<A, R>, Error> {
+    imp.downcast_ref::<Imp<A, R>>()
+        .ok_or_else(|| Error::SignatureMismatch {
+            selector: sel_name(sel).to_owned(),
+            expected: any::type_name::<Imp<A, R>>(),
+        })
+}
+
+/// Checks that `receiver` implements `sel` with argument types `A` and return
+/// type `R`.
+pub fn verify_message<A, R>(receiver: *const impl Sized, sel: Sel) -> Result<(), Error>
+where
+    A: 'static,
+    R: 'static,
+{
+    if receiver.is_null() {
+        return Ok(());
+    }
+    let imp = with_runtime(|runtime| runtime.lookup(receiver as usize, sel))?;
+    downcast::<A, R>(&imp, sel).map(|_| ())
+}
+
+/// The unwinding payload of `throw`.
+pub(crate) struct Thrown(pub(crate) usize);
+
+/// Throws `exception` from a method implementation, like `@throw`. It
+/// unwinds to the nearest `exception::catch`, and otherwise fails the test.
+pub fn throw(exception: *mut Object) -> ! {
+    retain(exception);
+    std::panic::resume_unwind(Box::new(Thrown(exception as usize)))
+}
+
+/// Records the message and calls the registered implementation. As with the
+/// real runtime, messaging nil returns zero.
+///
+/// # Safety
+///
+/// `R` must be valid when zeroed if `receiver` is null.
+pub unsafe fn send_message<T, A, R>(receiver: *const T, sel: Sel, args: A) -> Result<R, Error>
+where
+    A: Clone + 'static,
+    R: 'static,
+{
+    send(receiver as *mut Object, None, sel, args)
+}
+
+/// Like `send_message`, but looks up the implementation starting at
+/// `superclass`, as a message to `super` does.
+///
+/// # Safety
+///
+/// `R` must be valid when zeroed if `receiver` is null.
+pub unsafe fn send_super_message<T, A, R>(
+    receiver: *const T,
+    superclass: *const Class,
+    sel: Sel,
+    args: A,
+) -> Result<R, Error>
+where
+    A: Clone + 'static,
+    R: 'static,
+{
+    send(receiver as *mut Object, Some(superclass as usize), sel, args)
+}
+
+unsafe fn send<A, R>(
+    receiver: *mut Object,
+    superclass: Option<usize>,
+    sel: Sel,
+    args: A,
+) -> Result<R, Error>
+where
+    A: Clone + 'static,
+    R: 'static,
+{
+    let imp = with_runtime(|runtime| {
+        runtime.messages.push(SentMessage {
+            receiver,
+            selector: sel_name(sel),
+            args: Rc::new(args.clone()),
+        });
+        if receiver.is_null() {
+            return None;
+        }
+        Some(match superclass {
+            Some(superclass) => runtime
+                .receiver_class(receiver as usize, sel)
+                .and_then(|(_, is_class)| runtime.lookup_in(superclass, is_class, sel)),
+            None => runtime.lookup(receiver as usize, sel),
+        })
+    });
+    match imp {
+        // The runtime is no longer borrowed, so implementations may send messages themselves.
+        Some(imp) => {
+            let imp = imp?;
+            Ok(downcast::<A, R>(&imp, sel)?(receiver, args))
+        }
+        None => Ok(mem::zeroed()),
+    }
+}
@@ -0,0 +1,40 @@
+//! Errors reported through `NSError **` arguments.
+//!
+//! Cocoa methods ending in `error:` signal failure by returning `NO` or nil,
+//! and describe it through the error they store in their last argument.
+//! Flagging a binding `throws` hides that argument and turns the result into
+//! a `Result`:
+//!
+//! ```ignore
+//! #[objc(selector = "writeToFile:options:error:", throws, macos = "10.4", ios = "2")]
+//! pub fn nsdata_write_to_file(
+//!     data: *const NSData,
+//!     path: *const NSString,
+//!     options: NSUInteger,
+//! ) -> Result<(), Id<NSError>>;
+//! ```
+//!
+//! Bindings returning `Result<()>` fail on `NO`. Ones returning
+//! `Result<Id<T>>` or `Result<NonNull<T>>` fail on nil.
+
+use crate::{Encode, Encoding, Message};
+
+/// An `NSError` instance.
+#[repr(C)]
+pub struct NSError {
+    _private: [u8; 0],
+}
+
+unsafe impl Message for NSError {}
+
+/// The `error:` argument of a `throws` binding, encoded as the object
+/// pointer pointer it is.
+#[doc(hidden)]
+#[repr(transparent)]
+pub struct ErrorOut<E>(pub *mut *mut E);
+
+unsafe impl<E> Encode for ErrorOut<E> {
+    fn encode() -> Encoding {
+        unsafe { Encoding::from_str("^@") }
+    }
+}
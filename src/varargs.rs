@@ -0,0 +1,180 @@
+//! Messages with C variadic arguments, such as `stringWithFormat:`.
+//!
+//! A variadic binding takes its trailing arguments as a tuple of [`VarArg`]s.
+//! The message is sent through a function pointer typed as C-variadic, so the
+//! arguments follow the platform's variadic calling convention. On arm64 Apple
+//! platforms that means they are passed on the stack rather than in registers,
+//! unlike the fixed arguments before them. `catch_exceptions` bindings send
+//! through an `extern "C-unwind"` pointer instead, so that exceptions can
+//! unwind out of the message.
+
+use crate::runtime::{Imp, Object, Sel};
+use std::mem;
+
+/// A type that is passed through C varargs unchanged.
+///
+/// C promotes `char`, `short` and `float` arguments to `int` and `double`, so
+/// `i8`, `u8`, `i16`, `u16`, `f32` and `bool` (and with them `BOOL`) are not
+/// `VarArg`s. Cast them to `c_int` or `f64` first, as a C caller would.
+///
+/// # Safety
+///
+/// The type must be passed the same way through C varargs as the equivalent C
+/// type is after default argument promotion.
+pub unsafe trait VarArg: Copy + 'static {}
+
+unsafe impl VarArg for i32 {}
+unsafe impl VarArg for u32 {}
+unsafe impl VarArg for i64 {}
+unsafe impl VarArg for u64 {}
+unsafe impl VarArg for isize {}
+unsafe impl VarArg for usize {}
+unsafe impl VarArg for f64 {}
+unsafe impl<T: 'static> VarArg for *const T {}
+unsafe impl<T: 'static> VarArg for *mut T {}
+
+/// A tuple of [`VarArg`]s, passed as the variadic arguments of a binding.
+///
+/// # Safety
+///
+/// Implemented for tuples of up to twelve `VarArg`s; there's no need to
+/// implement it elsewhere.
+pub unsafe trait VarArgs: Copy + 'static {
+    #[doc(hidden)]
+    unsafe fn invoke<A: FixedArgs, R>(
+        self,
+        imp: Imp,
+        receiver: *mut Object,
+        sel: Sel,
+        fixed: A,
+        unwind: bool,
+    ) -> R;
+}
+
+/// Sends `sel` to `receiver` with the `fixed` arguments of the binding
+/// followed by `varargs`.
+#[doc(hidden)]
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub unsafe fn send<A: FixedArgs, V: VarArgs, R: crate::Encode + 'static>(
+    receiver: *mut Object,
+    sel: Sel,
+    fixed: A,
+    varargs: V,
+) -> R {
+    let imp = crate::backend::imp::<R>(receiver, sel);
+    varargs.invoke(imp, receiver, sel, fixed, false)
+}
+
+/// Like [`send`], but through an `extern "C-unwind"` function pointer, so
+/// exceptions may unwind out of it.
+#[doc(hidden)]
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+pub unsafe fn send_unwind<A: FixedArgs, V: VarArgs, R: crate::Encode + 'static>(
+    receiver: *mut Object,
+    sel: Sel,
+    fixed: A,
+    varargs: V,
+) -> R {
+    let imp = crate::backend::imp::<R>(receiver, sel);
+    varargs.invoke(imp, receiver, sel, fixed, true)
+}
+
+// Neither tuple can be spread into a call on its own, so `VarArgs::invoke`
+// dispatches on the number of variadic arguments to the `FixedArgs` method
+// that also knows the fixed ones.
+macro_rules! varargs_impls {
+    ($invokes:tt; $($fixed:tt)*) => {
+        varargs_impls!(@trait $invokes);
+        varargs_impls!(@var_args $invokes);
+        $(varargs_impls!(@fixed_args $fixed $invokes);)*
+    };
+    (@trait [$($invoke:ident($($b:ident),*))*]) => {
+        /// A tuple of the fixed arguments of a variadic binding.
+        #[doc(hidden)]
+        #[allow(non_snake_case, clippy::missing_safety_doc, clippy::too_many_arguments)]
+        pub unsafe trait FixedArgs {
+            $(unsafe fn $invoke<R, $($b: VarArg),*>(
+                self,
+                imp: Imp,
+                receiver: *mut Object,
+                sel: Sel,
+                unwind: bool,
+                $($b: $b),*
+            ) -> R;)*
+        }
+    };
+    (@var_args [$($invoke:ident($($b:ident),*))*]) => {$(
+        unsafe impl<$($b: VarArg),*> VarArgs for ($($b,)*) {
+            #[allow(non_snake_case)]
+            unsafe fn invoke<A: FixedArgs, R>(
+                self,
+                imp: Imp,
+                receiver: *mut Object,
+                sel: Sel,
+                fixed: A,
+                unwind: bool,
+            ) -> R {
+                let ($($b,)*) = self;
+                fixed.$invoke(imp, receiver, sel, unwind, $($b),*)
+            }
+        }
+    )*};
+    (@fixed_args $fixed:tt [$($invoke:ident $b:tt)*]) => {
+        varargs_impls!(@fixed_args_impl $fixed {
+            $(varargs_impls!(@invoke $fixed $invoke $b);)*
+        });
+    };
+    (@fixed_args_impl ($($a:ident),*) { $($body:tt)* }) => {
+        unsafe impl<$($a),*> FixedArgs for ($($a,)*) {
+            $($body)*
+        }
+    };
+    (@invoke ($($a:ident),*) $invoke:ident ($($b:ident),*)) => {
+        #[allow(non_snake_case, clippy::too_many_arguments)]
+        unsafe fn $invoke<R, $($b: VarArg),*>(
+            self,
+            imp: Imp,
+            receiver: *mut Object,
+            sel: Sel,
+            unwind: bool,
+            $($b: $b),*
+        ) -> R {
+            let ($($a,)*) = self;
+            if unwind {
+                let imp: unsafe extern "C-unwind" fn(*mut Object, Sel, $($a,)* ...) -> R =
+                    mem::transmute(imp);
+                imp(receiver, sel, $($a,)* $($b),*)
+            } else {
+                let imp: unsafe extern "C" fn(*mut Object, Sel, $($a,)* ...) -> R = mem::transmute(imp);
+                imp(receiver, sel, $($a,)* $($b),*)
+            }
+        }
+    };
+}
+
+varargs_impls! {
+    [
+        invoke0()
+        invoke1(B0)
+        invoke2(B0, B1)
+        invoke3(B0, B1, B2)
+        invoke4(B0, B1, B2, B3)
+        invoke5(B0, B1, B2, B3, B4)
+        invoke6(B0, B1, B2, B3, B4, B5)
+        invoke7(B0, B1, B2, B3, B4, B5, B6)
+        invoke8(B0, B1, B2, B3, B4, B5, B6, B7)
+        invoke9(B0, B1, B2, B3, B4, B5, B6, B7, B8)
+        invoke10(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9)
+        invoke11(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10)
+        invoke12(B0, B1, B2, B3, B4, B5, B6, B7, B8, B9, B10, B11)
+    ];
+    ()
+    (A0)
+    (A0, A1)
+    (A0, A1, A2)
+    (A0, A1, A2, A3)
+    (A0, A1, A2, A3, A4)
+    (A0, A1, A2, A3, A4, A5)
+    (A0, A1, A2, A3, A4, A5, A6)
+    (A0, A1, A2, A3, A4, A5, A6, A7)
+}
@@ -1,41 +1,231 @@
+#![cfg(any(target_vendor = "apple", feature = "gnustep", feature = "mock"))]
+
 use objc_util::*;
+use std::ptr::NonNull;
 
 #[extern_objc(framework = "Foundation")]
 extern "ObjC" {
     #[objc(selector = "alloc", macos = "10", ios = "2")]
-    pub fn nsobj_alloc(class: *const runtime::Class) -> *mut runtime::Object;
+    pub fn nsobj_alloc(class: *const runtime::Class) -> Id<runtime::Object>;
 
     #[objc(selector = "init", macos = "10", ios = "2")]
     #[inline(never)]
-    pub fn nsobj_init(obj: *mut runtime::Object) -> *mut runtime::Object;
+    pub fn nsobj_init(obj: Id<runtime::Object>) -> Option<Id<runtime::Object>>;
+
+    #[objc(selector = "init", super = "NSObject", macos = "10", ios = "2")]
+    pub fn nsobj_super_init(obj: Id<runtime::Object>) -> Option<Id<runtime::Object>>;
+
+    #[objc(selector = "hash", super, macos = "10", ios = "2")]
+    pub fn nsobj_super_hash(
+        obj: *const runtime::Object,
+        superclass: *const runtime::Class,
+    ) -> NSUInteger;
+
+    #[objc(selector = "self", macos = "10", ios = "2")]
+    pub fn nsobj_self(obj: *const runtime::Object) -> *mut runtime::Object;
+
+    #[objc(selector = "copy", macos = "10", ios = "2")]
+    pub fn nsobj_copy(obj: *const runtime::Object) -> Id<runtime::Object>;
 
-    #[objc(selector = "retain", macos = "10", ios = "2")]
-    pub fn nsobj_retain(obj: *mut runtime::Object) -> *mut runtime::Object;
+    #[objc(selector = "retain", macos = "10", ios = "2", returns_retained)]
+    pub fn nsobj_retain(obj: *mut runtime::Object) -> Id<runtime::Object>;
 
     #[objc(selector = "hash", macos = "10", ios = "2")]
     #[inline]
     pub fn nsobj_hash(obj: *const runtime::Object) -> NSUInteger;
 
     #[objc(selector = "isEqual:", macos = "10", ios = "2")]
-    pub fn nsobj_is_equal(
-        lhs: *const runtime::Object,
-        rhs: *const runtime::Object,
-    ) -> objc::runtime::BOOL;
+    pub fn nsobj_is_equal(lhs: *const runtime::Object, rhs: *const runtime::Object) -> bool;
 
-    #[objc(selector = "data", macos = "10", ios = "2")]
-    pub fn nsdata_data(class: *const runtime::Class) -> *mut runtime::Object;
+    #[objc(
+        selector = "homeDirectoryForUser:",
+        macos = "10.12",
+        ios = "unavailable",
+        tvos = "unavailable",
+        watchos = "unavailable"
+    )]
+    pub fn nsfile_manager_home_directory_for_user(
+        obj: *const runtime::Object,
+        user_name: *const runtime::Object,
+    ) -> Option<NonNull<runtime::Object>>;
+
+    #[objc(selector = "respondsToSelector:", macos = "10", ios = "2")]
+    pub fn nsobj_responds_to_selector(
+        obj: *const runtime::Object,
+        a_selector: runtime::Sel,
+    ) -> bool;
+
+    #[objc(
+        class = "NSProcessInfo",
+        selector = "processInfo",
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsprocess_info_process_info() -> *mut runtime::Object;
+
+    #[objc(selector = "operatingSystemVersion", macos = "10.10", ios = "8")]
+    pub fn nsprocess_info_operating_system_version(
+        obj: *const runtime::Object,
+    ) -> NSOperatingSystemVersion;
+
+    #[objc(class = "NSData", selector = "data", macos = "10", ios = "2", nonnull)]
+    pub fn nsdata_data() -> *mut runtime::Object;
 
     #[objc(selector = "initWithBytes:length:", macos = "10", ios = "2")]
     pub fn nsdata_init_with_bytes(
-        obj: *const runtime::Object,
+        obj: Id<runtime::Object>,
         bytes: *const std::os::raw::c_void,
         length: NSUInteger,
-    ) -> *mut runtime::Object;
+    ) -> Option<Id<runtime::Object>>;
 
-    #[objc(selector = "dataWithBytes:length:", macos = "10", ios = "2")]
+    #[objc(
+        class = "NSData",
+        selector = "dataWithBytes:length:",
+        lookup = "runtime",
+        macos = "10",
+        ios = "2"
+    )]
     pub fn nsdata_data_with_bytes(
-        class: *const runtime::Class,
         bytes: *const std::os::raw::c_void,
         length: NSUInteger,
-    ) -> *mut runtime::Object;
+    ) -> Id<runtime::Object>;
+
+    #[objc(class = "NSData", macos = "10", ios = "2")]
+    pub fn data_with_bytes__length(
+        bytes: *const std::os::raw::c_void,
+        length: NSUInteger,
+    ) -> Id<runtime::Object>;
+
+    #[objc(
+        class = "NSData",
+        selector = "dataWithContentsOfFile:options:error:",
+        throws,
+        macos = "10.4",
+        ios = "2"
+    )]
+    pub fn nsdata_data_with_contents_of_file(
+        path: *const runtime::Object,
+        options: NSUInteger,
+    ) -> Result<Id<runtime::Object>, Id<NSError>>;
+
+    #[objc(
+        selector = "writeToFile:options:error:",
+        throws,
+        macos = "10.4",
+        ios = "2"
+    )]
+    pub fn nsdata_write_to_file(
+        data: *const runtime::Object,
+        path: *const runtime::Object,
+        options: NSUInteger,
+    ) -> Result<(), Id<NSError>>;
+
+    #[objc(
+        class = "NSString",
+        selector = "stringWithUTF8String:",
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsstring_with_utf8_string(
+        string: *const std::os::raw::c_char,
+    ) -> Option<Id<runtime::Object>>;
+
+    #[objc(
+        class = "NSString",
+        selector = "stringWithFormat:",
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsstring_with_format(format: *const runtime::Object, ...) -> Id<runtime::Object>;
+
+    #[objc(
+        class = "NSNumber",
+        selector = "numberWithBool:",
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsnumber_with_bool(value: bool) -> *mut runtime::Object;
+
+    #[objc(selector = "boolValue", macos = "10", ios = "2")]
+    pub fn nsnumber_bool_value(number: *const runtime::Object) -> bool;
+
+    #[objc(
+        class = "NSArray",
+        selector = "arrayWithObject:",
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsarray_array_with_object(object: *const runtime::Object) -> NonNull<runtime::Object>;
+
+    #[objc(property = "length", macos = "10", ios = "2")]
+    pub fn nsmutable_data_length(data: *const runtime::Object) -> NSUInteger;
+
+    #[objc(
+        property = "cancellable",
+        getter = "isCancellable",
+        macos = "10.9",
+        ios = "7"
+    )]
+    pub fn nsprogress_cancellable(progress: *const runtime::Object) -> bool;
+
+    #[objc(property = "name", macos = "10.5", ios = "2")]
+    pub fn nsthread_name(thread: *const runtime::Object) -> Option<Id<runtime::Object>>;
+
+    #[objc(selector = "enumerateObjectsUsingBlock:", macos = "10.6", ios = "4")]
+    pub fn nsarray_enumerate_objects(
+        array: *const runtime::Object,
+        block: impl Fn(*const runtime::Object, NSUInteger, *mut runtime::BOOL),
+    );
+}
+
+pub struct NSObject {
+    _priv: [u8; 0],
+}
+
+unsafe impl Message for NSObject {}
+
+#[extern_objc(framework = "Foundation")]
+impl NSObject {
+    #[objc(macos = "10", ios = "2")]
+    pub fn hash(&self) -> NSUInteger;
+
+    #[objc(macos = "10", ios = "2")]
+    pub fn is_equal(&self, other: *const runtime::Object) -> bool;
+
+    #[objc(property = "description", readonly, macos = "10", ios = "2")]
+    pub fn description(&self) -> Id<runtime::Object>;
+}
+
+#[cfg(any(feature = "exception", feature = "mock"))]
+#[extern_objc(framework = "Foundation")]
+extern "ObjC" {
+    #[objc(selector = "objectAtIndex:", catch_exceptions, macos = "10", ios = "2")]
+    pub fn nsarray_object_at_index(
+        array: *const runtime::Object,
+        index: NSUInteger,
+    ) -> Result<*mut runtime::Object, Id<exception::NSException>>;
+
+    #[objc(
+        selector = "objectAtIndex:",
+        super = "NSArray",
+        catch_exceptions,
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsarray_super_object_at_index(
+        array: *const runtime::Object,
+        index: NSUInteger,
+    ) -> Result<*mut runtime::Object, Id<exception::NSException>>;
+
+    #[objc(
+        class = "NSString",
+        selector = "stringWithFormat:",
+        catch_exceptions,
+        macos = "10",
+        ios = "2"
+    )]
+    pub fn nsstring_try_with_format(
+        format: *const runtime::Object,
+        ...
+    ) -> Result<Id<runtime::Object>, Id<exception::NSException>>;
 }
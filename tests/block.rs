@@ -0,0 +1,122 @@
+#![cfg(any(target_vendor = "apple", feature = "gnustep", feature = "mock"))]
+
+use objc_util::{
+    block::{Block, StackBlock},
+    Encode,
+};
+use std::{
+    mem,
+    os::raw::{c_int, c_ulong, c_void},
+    ptr,
+    rc::Rc,
+};
+
+// The block literal and descriptor layouts from the Apple block ABI.
+#[repr(C)]
+struct BlockLiteral {
+    isa:        *const c_void,
+    flags:      c_int,
+    reserved:   c_int,
+    invoke:     unsafe extern "C" fn(),
+    descriptor: *const BlockDescriptor,
+}
+
+#[repr(C)]
+struct BlockDescriptor {
+    reserved: c_ulong,
+    size:     c_ulong,
+    copy:     unsafe extern "C" fn(*mut c_void, *const c_void),
+    dispose:  unsafe extern "C" fn(*mut c_void),
+}
+
+const BLOCK_HAS_COPY_DISPOSE: c_int = 1 << 25;
+
+fn literal<A, R, F>(block: &StackBlock<A, R, F>) -> &BlockLiteral {
+    unsafe { &*(block as *const _ as *const BlockLiteral) }
+}
+
+#[test]
+fn call() {
+    let offset = 10;
+    let block = StackBlock::new(move |a: i32, b: i32| a * b + offset);
+    assert_eq!(unsafe { block.call((4, 8)) }, 42);
+}
+
+#[test]
+fn layout() {
+    let value = 42u64;
+    let block = StackBlock::new(move || value);
+    let literal = literal(&block);
+    assert_eq!(literal.flags, BLOCK_HAS_COPY_DISPOSE);
+    assert_eq!(literal.reserved, 0);
+    unsafe {
+        let descriptor = &*literal.descriptor;
+        assert_eq!(descriptor.reserved, 0);
+        assert_eq!(descriptor.size as usize, mem::size_of_val(&block));
+        assert_eq!(
+            mem::size_of_val(&block),
+            mem::size_of::<BlockLiteral>() + mem::size_of::<u64>()
+        );
+
+        // Callers pass the block itself as the first argument.
+        let invoke: unsafe extern "C" fn(*const BlockLiteral) -> u64 =
+            mem::transmute(literal.invoke);
+        assert_eq!(invoke(literal), 42);
+    }
+}
+
+#[test]
+fn copy_dispose() {
+    let captured = Rc::new(7);
+    let block = {
+        let captured = captured.clone();
+        StackBlock::new(move |x: i32| x + *captured)
+    };
+    assert_eq!(Rc::strong_count(&captured), 2);
+    unsafe {
+        // What `_Block_copy` does with a stack block.
+        let literal = literal(&block);
+        let descriptor = &*literal.descriptor;
+        let mut heap = vec![0u64; descriptor.size as usize / mem::size_of::<u64>()];
+        let dst = heap.as_mut_ptr() as *mut c_void;
+        ptr::copy_nonoverlapping(
+            literal as *const _ as *const u8,
+            dst as *mut u8,
+            descriptor.size as usize,
+        );
+        (descriptor.copy)(dst, literal as *const _ as *const c_void);
+        assert_eq!(Rc::strong_count(&captured), 3);
+
+        let copy = &*(dst as *const Block<(i32,), i32>);
+        assert_eq!(copy.call((1,)), 8);
+
+        (descriptor.dispose)(dst);
+        assert_eq!(Rc::strong_count(&captured), 2);
+    }
+    drop(block);
+    assert_eq!(Rc::strong_count(&captured), 1);
+}
+
+#[cfg(any(target_vendor = "apple", feature = "gnustep"))]
+#[test]
+fn copy_outlives_stack_block() {
+    let captured = Rc::new(7);
+    let copy = {
+        let captured = captured.clone();
+        let block = StackBlock::new(move |x: i32| x + *captured);
+        block.copy()
+    };
+    // The heap copy holds its own clone of the closure.
+    assert_eq!(Rc::strong_count(&captured), 2);
+    assert_eq!(unsafe { copy.call((1,)) }, 8);
+    let clone = copy.clone();
+    drop(copy);
+    assert_eq!(unsafe { clone.call((2,)) }, 9);
+    drop(clone);
+    assert_eq!(Rc::strong_count(&captured), 1);
+}
+
+#[test]
+fn encoding() {
+    assert_eq!(<&Block<(i32,), ()>>::encode().as_str(), "@?");
+}
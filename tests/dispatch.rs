@@ -0,0 +1,134 @@
+#![cfg(any(target_vendor = "apple", feature = "gnustep", feature = "mock"))]
+
+use objc_util::{
+    dispatch::{Arch, MsgSend, ReturnKind, ReturnLayout},
+    runtime, NSOperatingSystemVersion, NSUInteger,
+};
+use std::os::raw::c_void;
+
+fn select(triple: &str, ret: ReturnLayout) -> MsgSend {
+    MsgSend::select(Arch::from_triple(triple).unwrap(), ret)
+}
+
+fn layout(size: usize, encoding: &str) -> ReturnLayout {
+    ReturnLayout::from_encoding(size, encoding)
+}
+
+#[test]
+fn triples() {
+    assert_eq!(Arch::from_triple("i686-apple-darwin"), Some(Arch::X86));
+    assert_eq!(Arch::from_triple("i386-apple-ios"), Some(Arch::X86));
+    assert_eq!(Arch::from_triple("x86_64-apple-darwin"), Some(Arch::X86_64));
+    assert_eq!(Arch::from_triple("x86_64-apple-tvos"), Some(Arch::X86_64));
+    assert_eq!(Arch::from_triple("armv7-apple-ios"), Some(Arch::Arm));
+    assert_eq!(Arch::from_triple("armv7k-apple-watchos"), Some(Arch::Arm));
+    assert_eq!(
+        Arch::from_triple("aarch64-apple-visionos"),
+        Some(Arch::Aarch64)
+    );
+    assert_eq!(
+        Arch::from_triple("arm64e-apple-darwin"),
+        Some(Arch::Aarch64)
+    );
+    assert_eq!(
+        Arch::from_triple("arm64_32-apple-watchos"),
+        Some(Arch::Aarch64)
+    );
+    assert_eq!(Arch::from_triple("riscv64gc-unknown-linux-gnu"), None);
+}
+
+#[test]
+fn return_layouts() {
+    assert_eq!(
+        ReturnLayout::of::<NSOperatingSystemVersion>(),
+        ReturnLayout {
+            size: 24,
+            kind: ReturnKind::Aggregate,
+        }
+    );
+    assert_eq!(ReturnLayout::of::<f64>().kind, ReturnKind::Float);
+    assert_eq!(ReturnLayout::of::<f32>().kind, ReturnKind::Float);
+    assert_eq!(ReturnLayout::of::<NSUInteger>().kind, ReturnKind::Scalar);
+    assert_eq!(ReturnLayout::of::<*const c_void>().kind, ReturnKind::Scalar);
+    assert_eq!(
+        ReturnLayout::of::<*mut runtime::Object>().kind,
+        ReturnKind::Scalar
+    );
+    assert_eq!(ReturnLayout::of::<()>().kind, ReturnKind::Scalar);
+    assert_eq!(layout(8, "r{?=ii}").kind, ReturnKind::Aggregate);
+    assert_eq!(layout(16, "D").kind, ReturnKind::LongDouble);
+}
+
+#[test]
+fn cached_variants() {
+    fn selected<R: objc_util::Encode>() -> MsgSend {
+        MsgSend::select(Arch::current().unwrap(), ReturnLayout::of::<R>())
+    }
+
+    // The second round is answered from the cache.
+    for _ in 0..2 {
+        assert_eq!(
+            MsgSend::of::<NSOperatingSystemVersion>(),
+            selected::<NSOperatingSystemVersion>()
+        );
+        assert_eq!(MsgSend::of::<f64>(), selected::<f64>());
+        assert_eq!(MsgSend::of::<NSUInteger>(), selected::<NSUInteger>());
+        assert_eq!(MsgSend::of::<()>(), selected::<()>());
+    }
+}
+
+#[test]
+fn x86_64() {
+    let triple = "x86_64-apple-darwin";
+    // NSRange, CGRect and NSOperatingSystemVersion.
+    assert_eq!(select(triple, layout(16, "{_NSRange=QQ}")), MsgSend::Normal);
+    assert_eq!(
+        select(triple, layout(32, "{CGRect={CGPoint=dd}{CGSize=dd}}")),
+        MsgSend::Stret
+    );
+    assert_eq!(select(triple, layout(24, "{?=qqq}")), MsgSend::Stret);
+    assert_eq!(select(triple, layout(8, "d")), MsgSend::Normal);
+    assert_eq!(select(triple, layout(16, "D")), MsgSend::Fpret);
+    assert_eq!(select(triple, layout(8, "Q")), MsgSend::Normal);
+}
+
+#[test]
+fn x86() {
+    let triple = "i686-apple-darwin";
+    assert_eq!(select(triple, layout(8, "{_NSRange=II}")), MsgSend::Normal);
+    assert_eq!(select(triple, layout(2, "{?=cc}")), MsgSend::Normal);
+    assert_eq!(select(triple, layout(3, "{?=ccc}")), MsgSend::Stret);
+    assert_eq!(select(triple, layout(12, "{?=iii}")), MsgSend::Stret);
+    assert_eq!(
+        select(triple, layout(16, "{CGRect={CGPoint=ff}{CGSize=ff}}")),
+        MsgSend::Stret
+    );
+    assert_eq!(select(triple, layout(4, "f")), MsgSend::Fpret);
+    assert_eq!(select(triple, layout(8, "d")), MsgSend::Fpret);
+    assert_eq!(select(triple, layout(8, "q")), MsgSend::Normal);
+}
+
+#[test]
+fn arm() {
+    let triple = "armv7-apple-ios";
+    assert_eq!(select(triple, layout(4, "{?=s}")), MsgSend::Normal);
+    assert_eq!(select(triple, layout(8, "{_NSRange=II}")), MsgSend::Stret);
+    assert_eq!(select(triple, layout(8, "d")), MsgSend::Normal);
+    assert_eq!(select(triple, layout(8, "q")), MsgSend::Normal);
+}
+
+#[test]
+fn aarch64() {
+    for triple in &[
+        "aarch64-apple-ios",
+        "aarch64-apple-tvos",
+        "arm64_32-apple-watchos",
+    ] {
+        assert_eq!(
+            select(triple, layout(32, "{CGRect={CGPoint=dd}{CGSize=dd}}")),
+            MsgSend::Normal
+        );
+        assert_eq!(select(triple, layout(8, "d")), MsgSend::Normal);
+        assert_eq!(select(triple, layout(16, "D")), MsgSend::Normal);
+    }
+}
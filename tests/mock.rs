@@ -0,0 +1,611 @@
+#![cfg(feature = "mock")]
+
+use objc_util::{
+    block::Block, class, class_opt, exception, extern_objc, mock, runtime, Id, NSError,
+    NSOperatingSystemVersion, NSUInteger, Unavailable, WeakId, OS_VERSION,
+};
+use std::{
+    cell::Cell,
+    os::raw::{c_int, c_void},
+    ptr,
+    rc::Rc,
+};
+use test_lib::*;
+
+#[test]
+fn dispatch() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_class_method(nsdata, "alloc", |_, (): ()| mock::alloc(class!(NSData)));
+    mock::add_method(
+        nsdata,
+        "initWithBytes:length:",
+        |obj, (_, _): (*const c_void, NSUInteger)| obj,
+    );
+    mock::add_method(nsdata, "hash", |_, (): ()| -> NSUInteger { 42 });
+    unsafe {
+        let data = "aaaaa";
+        let obj = nsobj_alloc(class!(NSData));
+        let obj = nsdata_init_with_bytes(obj, data.as_ptr() as _, data.len() as _).unwrap();
+        assert_eq!(nsobj_hash(&*obj), 42);
+
+        let messages = mock::messages();
+        let selectors = messages.iter().map(|m| m.selector()).collect::<Vec<_>>();
+        assert_eq!(selectors, ["alloc", "initWithBytes:length:", "hash"]);
+        assert_eq!(messages[0].receiver(), class!(NSData) as *const _ as *mut _);
+        assert_eq!(messages[1].receiver(), Id::as_ptr(&obj));
+        assert_eq!(
+            messages[1].args::<(*const c_void, NSUInteger)>(),
+            Some(&(data.as_ptr() as _, data.len() as _))
+        );
+    }
+}
+
+#[test]
+fn inherited_methods() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    let nsdata = mock::register_subclass("NSData", nsobject);
+    mock::add_method(
+        nsobject,
+        "isEqual:",
+        |lhs, (rhs,): (*const runtime::Object,)| {
+            if std::ptr::eq(lhs, rhs) {
+                runtime::YES
+            } else {
+                runtime::NO
+            }
+        },
+    );
+    unsafe {
+        let obj = mock::alloc(nsdata);
+        assert!(nsobj_is_equal(obj, obj));
+        assert!(!nsobj_is_equal(obj, mock::alloc(nsdata)));
+    }
+}
+
+#[test]
+fn super_messages() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    let nsdata = mock::register_subclass("NSData", nsobject);
+    mock::add_method(nsobject, "hash", |_, (): ()| -> NSUInteger { 1 });
+    mock::add_method(nsdata, "hash", |_, (): ()| -> NSUInteger { 2 });
+    mock::add_method(nsobject, "init", |obj, (): ()| obj);
+    mock::add_method(nsdata, "init", |_, (): ()| {
+        ptr::null_mut::<runtime::Object>()
+    });
+    unsafe {
+        let obj = mock::alloc(nsdata);
+        assert_eq!(nsobj_hash(obj), 2);
+        assert_eq!(nsobj_super_hash(obj, nsobject), 1);
+        assert_eq!(nsobj_super_hash(obj, nsdata), 2);
+
+        let obj = nsobj_super_init(Id::from_retained_ptr(obj).unwrap()).unwrap();
+        assert_eq!(mock::retain_count(Id::as_ptr(&obj)), Some(1));
+        assert_eq!(
+            mock::messages().last().unwrap().receiver(),
+            Id::as_ptr(&obj)
+        );
+    }
+}
+
+#[test]
+#[should_panic(expected = "unrecognized selector -[NSData hash]")]
+fn unrecognized_selector() {
+    mock::reset();
+    let obj = mock::alloc(mock::register_class("NSData"));
+    unsafe {
+        nsobj_hash(obj);
+    }
+}
+
+#[test]
+#[should_panic(expected = "Binding error on `nsobj_hash`")]
+fn signature_mismatch() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_method(nsdata, "hash", |_, (): ()| -> i32 { 42 });
+    unsafe {
+        nsobj_hash(mock::alloc(nsdata));
+    }
+}
+
+#[test]
+fn try_wrapper() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_method(nsdata, "hash", |_, (): ()| -> NSUInteger { 42 });
+    unsafe {
+        // Availability keys don't apply to the mock runtime.
+        assert_eq!(try_nsobj_hash(mock::alloc(nsdata)), Ok(42));
+    }
+}
+
+#[test]
+fn unavailable_check() {
+    let found = *OS_VERSION;
+    let next = NSOperatingSystemVersion {
+        patch: found.patch + 1,
+        ..found
+    };
+    assert_eq!(Unavailable::check(found, None), Ok(()));
+    assert_eq!(Unavailable::check(found, Some(next)), Ok(()));
+    assert_eq!(
+        Unavailable::check(next, None),
+        Err(Unavailable {
+            required: next,
+            obsoleted: None,
+            found,
+        })
+    );
+    let unavailable = Unavailable::check(found, Some(found)).unwrap_err();
+    assert_eq!(unavailable.obsoleted, Some(found));
+    assert_eq!(
+        unavailable.to_string(),
+        format!("obsoleted in `{}` but found `{}`", found, found)
+    );
+}
+
+#[test]
+fn method_bindings() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    mock::add_method(nsobject, "hash", |_, (): ()| -> NSUInteger { 42 });
+    unsafe {
+        let obj = &*(mock::alloc(nsobject) as *const NSObject);
+        assert_eq!(obj.hash(), 42);
+        assert_eq!(obj.try_hash(), Ok(42));
+        assert_eq!(
+            mock::messages()[0].receiver(),
+            obj as *const NSObject as *mut _
+        );
+    }
+}
+
+#[test]
+fn bool_bindings() {
+    mock::reset();
+    let nsnumber = mock::register_class("NSNumber");
+    mock::add_class_method(nsnumber, "numberWithBool:", |_, (_,): (runtime::BOOL,)| {
+        mock::alloc(class!(NSNumber))
+    });
+    mock::add_method(nsnumber, "boolValue", |_, (): ()| runtime::YES);
+    unsafe {
+        let number = nsnumber_with_bool(true);
+        nsnumber_with_bool(false);
+        assert!(nsnumber_bool_value(number));
+
+        let messages = mock::messages();
+        assert_eq!(messages[0].args(), Some(&(runtime::YES,)));
+        assert_eq!(messages[1].args(), Some(&(runtime::NO,)));
+    }
+}
+
+#[test]
+fn nullability() {
+    mock::reset();
+    let nsarray = mock::register_class("NSArray");
+    let nsfile_manager = mock::register_class("NSFileManager");
+    mock::add_class_method(
+        nsarray,
+        "arrayWithObject:",
+        |_, (_,): (*const runtime::Object,)| mock::alloc(class!(NSArray)),
+    );
+    mock::add_method(
+        nsfile_manager,
+        "homeDirectoryForUser:",
+        |_, (user_name,): (*const runtime::Object,)| {
+            if user_name.is_null() {
+                ptr::null_mut()
+            } else {
+                mock::alloc(class!(NSString))
+            }
+        },
+    );
+    unsafe {
+        let array = nsarray_array_with_object(ptr::null());
+        assert_eq!(
+            mock::messages()[0].receiver(),
+            nsarray as *const _ as *mut _
+        );
+
+        let file_manager = mock::alloc(nsfile_manager);
+        assert_eq!(
+            nsfile_manager_home_directory_for_user(file_manager, ptr::null()),
+            None
+        );
+        mock::register_class("NSString");
+        assert!(nsfile_manager_home_directory_for_user(file_manager, array.as_ptr()).is_some());
+    }
+}
+
+#[test]
+#[should_panic(expected = "`nsdata_data` returned nil")]
+fn nonnull_returning_nil() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_class_method(nsdata, "data", |_, (): ()| {
+        ptr::null_mut::<runtime::Object>()
+    });
+    unsafe {
+        nsdata_data();
+    }
+}
+
+#[test]
+#[should_panic(expected = "`nsarray_array_with_object` returned nil")]
+fn nonnull_pointer_returning_nil() {
+    mock::reset();
+    let nsarray = mock::register_class("NSArray");
+    mock::add_class_method(
+        nsarray,
+        "arrayWithObject:",
+        |_, (_,): (*const runtime::Object,)| ptr::null_mut::<runtime::Object>(),
+    );
+    unsafe {
+        nsarray_array_with_object(ptr::null());
+    }
+}
+
+#[test]
+fn ownership() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    mock::add_class_method(nsobject, "alloc", |_, (): ()| mock::alloc(class!(NSObject)));
+    mock::add_method(nsobject, "init", |obj, (): ()| obj);
+    unsafe {
+        let obj = nsobj_init(nsobj_alloc(nsobject)).unwrap();
+        let ptr = Id::as_ptr(&obj);
+        assert_eq!(mock::retain_count(ptr), Some(1));
+
+        let weak = WeakId::new(&obj);
+        let clone = obj.clone();
+        assert_eq!(mock::retain_count(ptr), Some(2));
+        drop(clone);
+        assert_eq!(weak.load().as_ref().map(Id::as_ptr), Some(ptr));
+        assert_eq!(mock::retain_count(ptr), Some(1));
+
+        drop(obj);
+        assert_eq!(mock::retain_count(ptr), None);
+        assert!(weak.load().is_none());
+    }
+}
+
+#[test]
+fn method_families() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    mock::add_method(nsobject, "copy", |_, (): ()| mock::alloc(class!(NSObject)));
+    mock::add_method(nsobject, "retain", |obj, (): ()| mock::retain(obj));
+    unsafe {
+        let obj = mock::alloc(nsobject);
+        let copy = nsobj_copy(obj);
+        assert_eq!(mock::retain_count(Id::as_ptr(&copy)), Some(1));
+
+        let retained = nsobj_retain(obj);
+        assert_eq!(mock::retain_count(obj), Some(2));
+        drop(retained);
+        assert_eq!(mock::retain_count(obj), Some(1));
+    }
+}
+
+#[test]
+fn properties() {
+    mock::reset();
+    let nsmutable_data = mock::register_class("NSMutableData");
+    let nsprogress = mock::register_class("NSProgress");
+    let nsthread = mock::register_class("NSThread");
+    mock::add_method(nsmutable_data, "length", |_, (): ()| -> NSUInteger { 3 });
+    mock::add_method(nsmutable_data, "setLength:", |_, (_,): (NSUInteger,)| {});
+    mock::add_method(nsprogress, "isCancellable", |_, (): ()| runtime::NO);
+    mock::add_method(nsprogress, "setCancellable:", |_, (_,): (runtime::BOOL,)| {});
+    mock::add_method(nsthread, "name", |_, (): ()| -> *mut runtime::Object {
+        ptr::null_mut()
+    });
+    mock::add_method(nsthread, "setName:", |_, (_,): (*mut runtime::Object,)| {});
+    unsafe {
+        let data = mock::alloc(nsmutable_data);
+        assert_eq!(nsmutable_data_length(data), 3);
+        set_nsmutable_data_length(data, 5);
+
+        let progress = mock::alloc(nsprogress);
+        assert!(!nsprogress_cancellable(progress));
+        set_nsprogress_cancellable(progress, true);
+
+        let thread = mock::alloc(nsthread);
+        assert!(nsthread_name(thread).is_none());
+        set_nsthread_name(thread, data);
+
+        let messages = mock::messages();
+        let selectors = messages.iter().map(|m| m.selector()).collect::<Vec<_>>();
+        assert_eq!(
+            selectors,
+            [
+                "length",
+                "setLength:",
+                "isCancellable",
+                "setCancellable:",
+                "name",
+                "setName:"
+            ]
+        );
+        assert_eq!(messages[1].args::<(NSUInteger,)>(), Some(&(5,)));
+        assert_eq!(messages[3].args::<(runtime::BOOL,)>(), Some(&(runtime::YES,)));
+        assert_eq!(messages[5].args::<(*mut runtime::Object,)>(), Some(&(data,)));
+    }
+}
+
+#[test]
+fn readonly_properties() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    let description = mock::alloc(nsobject);
+    mock::add_method(nsobject, "description", move |_, (): ()| description);
+    unsafe {
+        let obj = &*(mock::alloc(nsobject) as *const NSObject);
+        assert_eq!(Id::as_ptr(&obj.description()), description);
+        assert_eq!(mock::messages()[0].selector(), "description");
+    }
+}
+
+#[test]
+fn autoreleased_returns() {
+    mock::reset();
+    let nsstring = mock::register_class("NSString");
+    let string = mock::alloc(nsstring);
+    mock::add_class_method(
+        nsstring,
+        "stringWithUTF8String:",
+        move |_, (_,): (*const std::os::raw::c_char,)| string,
+    );
+    unsafe {
+        let id = nsstring_with_utf8_string(b"abc\0".as_ptr() as _).unwrap();
+        assert_eq!(Id::as_ptr(&id), string);
+        assert_eq!(mock::retain_count(string), Some(2));
+        drop(id);
+        assert_eq!(mock::retain_count(string), Some(1));
+    }
+}
+
+#[test]
+fn class_bindings() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_class_method(
+        nsdata,
+        "dataWithBytes:length:",
+        |_, (_, _): (*const c_void, NSUInteger)| mock::alloc(class!(NSData)),
+    );
+    unsafe {
+        let data = "aaaaa";
+        nsdata_data_with_bytes(data.as_ptr() as _, data.len() as _);
+
+        let messages = mock::messages();
+        assert_eq!(messages[0].receiver(), nsdata as *const _ as *mut _);
+        assert_eq!(messages[0].selector(), "dataWithBytes:length:");
+    }
+}
+
+#[extern_objc(framework = "Foundation")]
+extern "ObjC" {
+    #[objc(selector = "setValue::", macos = "10", ios = "2")]
+    fn mock_set_value(obj: *mut runtime::Object, key: NSUInteger, value: NSUInteger);
+
+    #[objc(selector = ":", macos = "10", ios = "2")]
+    fn mock_colon(obj: *mut runtime::Object, value: NSUInteger) -> NSUInteger;
+}
+
+#[test]
+fn selector_grammar() {
+    mock::reset();
+    let nsobject = mock::register_class("NSObject");
+    mock::add_method(nsobject, "self", |obj, (): ()| obj);
+    mock::add_method(nsobject, "setValue::", |_, (_, _): (NSUInteger, NSUInteger)| {});
+    mock::add_method(nsobject, ":", |_, (value,): (NSUInteger,)| value + 1);
+    unsafe {
+        let obj = mock::alloc(nsobject);
+        assert_eq!(nsobj_self(obj), obj);
+        mock_set_value(obj, 1, 2);
+        assert_eq!(mock_colon(obj, 1), 2);
+        assert_eq!(try_mock_set_value(obj, 1, 2), Ok(()));
+        assert_eq!(try_mock_colon(obj, 2), Ok(3));
+
+        let messages = mock::messages();
+        let selectors = messages.iter().map(|m| m.selector()).collect::<Vec<_>>();
+        assert_eq!(selectors, ["self", "setValue::", ":", "setValue::", ":"]);
+        assert_eq!(messages[1].args::<(NSUInteger, NSUInteger)>(), Some(&(1, 2)));
+    }
+}
+
+#[test]
+fn derived_selectors() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_class_method(
+        nsdata,
+        "dataWithBytes:length:",
+        |_, (_, _): (*const c_void, NSUInteger)| mock::alloc(class!(NSData)),
+    );
+    mock::add_method(nsdata, "hash", |_, (): ()| -> NSUInteger { 42 });
+    mock::add_method(nsdata, "isEqual:", |_, (_,): (*const runtime::Object,)| {
+        runtime::YES
+    });
+    unsafe {
+        let data = data_with_bytes__length(ptr::null(), 0);
+        let obj = &*(Id::as_ptr(&data) as *const NSObject);
+        assert_eq!(obj.hash(), 42);
+        assert!(obj.is_equal(Id::as_ptr(&data)));
+
+        let messages = mock::messages();
+        let selectors = messages.iter().map(|m| m.selector()).collect::<Vec<_>>();
+        assert_eq!(selectors, ["dataWithBytes:length:", "hash", "isEqual:"]);
+    }
+}
+
+#[test]
+fn class_lookup() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData") as *const runtime::Class;
+    assert_eq!(class!(runtime NSData) as *const _, nsdata);
+    assert_eq!(class!(static NSData) as *const _, nsdata);
+}
+
+#[test]
+fn optional_classes() {
+    mock::reset();
+    assert!(class_opt!(NSData).is_none());
+    assert!(class!(NSData, macos = "11.0", ios = "14").is_none());
+    let nsdata = mock::register_class("NSData") as *const runtime::Class;
+    assert_eq!(class_opt!(NSData).unwrap() as *const _, nsdata);
+    assert_eq!(
+        class_opt!(runtime NSData, ios = "14").unwrap() as *const _,
+        nsdata
+    );
+    assert_eq!(class!(NSData, ios = "14").unwrap() as *const _, nsdata);
+}
+
+#[test]
+fn throwing_bindings() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    let nserror = mock::register_class("NSError");
+    let error = mock::alloc(nserror) as *mut NSError;
+    mock::add_class_method(
+        nsdata,
+        "dataWithContentsOfFile:options:error:",
+        move |_, (_, options, out): (*const runtime::Object, NSUInteger, *mut *mut NSError)| {
+            if options == 0 {
+                return mock::alloc(class!(NSData));
+            }
+            unsafe { *out = error };
+            ptr::null_mut()
+        },
+    );
+    mock::add_method(
+        nsdata,
+        "writeToFile:options:error:",
+        move |_, (_, options, out): (*const runtime::Object, NSUInteger, *mut *mut NSError)| {
+            if options == 0 {
+                return runtime::YES;
+            }
+            unsafe { *out = error };
+            runtime::NO
+        },
+    );
+    unsafe {
+        let path = mock::alloc(nsdata);
+        let data = nsdata_data_with_contents_of_file(path, 0).unwrap();
+        assert!(nsdata_write_to_file(&*data, path, 0).is_ok());
+
+        let err = nsdata_data_with_contents_of_file(path, 1).unwrap_err();
+        assert_eq!(Id::as_ptr(&err), error);
+        let err = nsdata_write_to_file(&*data, path, 1).unwrap_err();
+        assert_eq!(Id::as_ptr(&err), error);
+    }
+}
+
+#[test]
+#[should_panic(expected = "`nsdata_write_to_file` failed without an error")]
+fn throwing_without_error() {
+    mock::reset();
+    let nsdata = mock::register_class("NSData");
+    mock::add_method(
+        nsdata,
+        "writeToFile:options:error:",
+        |_, (_, _, _): (*const runtime::Object, NSUInteger, *mut *mut NSError)| runtime::NO,
+    );
+    unsafe {
+        let data = mock::alloc(nsdata);
+        let _ = nsdata_write_to_file(data, data, 0);
+    }
+}
+
+#[test]
+fn catching_exceptions() {
+    mock::reset();
+    let nsarray = mock::register_class("NSArray");
+    let nsexception = mock::register_class("NSException");
+    let nsstring = mock::register_class("NSString");
+    let name = mock::alloc(nsstring);
+    let reason = mock::alloc(nsstring);
+    mock::add_method(nsexception, "name", move |_, (): ()| name);
+    mock::add_method(nsexception, "reason", move |_, (): ()| reason);
+    mock::add_method(nsexception, "userInfo", |_, (): ()| {
+        ptr::null_mut::<runtime::Object>()
+    });
+    let thrown = mock::alloc(nsexception);
+    mock::add_method(
+        nsarray,
+        "objectAtIndex:",
+        move |obj, (index,): (NSUInteger,)| {
+            if index > 0 {
+                mock::throw(thrown);
+            }
+            obj
+        },
+    );
+    unsafe {
+        let array = mock::alloc(nsarray);
+        assert_eq!(nsarray_object_at_index(array, 0).ok(), Some(array));
+
+        let exception = nsarray_object_at_index(array, 1).unwrap_err();
+        assert_eq!(Id::as_ptr(&exception) as *mut runtime::Object, thrown);
+        assert_eq!(mock::retain_count(thrown), Some(2));
+        assert_eq!(exception.name().as_ref().map(Id::as_ptr), Some(name));
+        assert_eq!(exception.reason().as_ref().map(Id::as_ptr), Some(reason));
+        assert!(exception.user_info().is_none());
+    }
+}
+
+#[test]
+fn catch_passes_panics_through() {
+    let result =
+        std::panic::catch_unwind(|| unsafe { exception::catch(|| panic!("not an exception")) });
+    assert!(result.is_err());
+}
+
+#[test]
+fn variadic_bindings() {
+    mock::reset();
+    let nsstring = mock::register_class("NSString");
+    mock::add_class_method(
+        nsstring,
+        "stringWithFormat:",
+        |_, (_, (n, x)): (*const runtime::Object, (c_int, f64))| {
+            assert_eq!((n, x), (42, 0.5));
+            mock::alloc(class!(NSString))
+        },
+    );
+    unsafe {
+        let format = mock::alloc(nsstring);
+        nsstring_with_format(format, (42 as c_int, 0.5f64));
+    }
+}
+
+#[test]
+fn block_arguments() {
+    mock::reset();
+    let nsarray = mock::register_class("NSArray");
+    mock::add_method(
+        nsarray,
+        "enumerateObjectsUsingBlock:",
+        |obj, (block,): (*const Block<(*const runtime::Object, NSUInteger, *mut runtime::BOOL), ()>,)| {
+            for idx in 0..3 {
+                unsafe { (*block).call((obj, idx, ptr::null_mut())) }
+            }
+        },
+    );
+    unsafe {
+        let array = mock::alloc(nsarray);
+        let sum = Rc::new(Cell::new(0));
+        nsarray_enumerate_objects(array, {
+            let sum = sum.clone();
+            move |obj, idx, _| {
+                assert_eq!(obj, array as *const _);
+                sum.set(sum.get() + idx);
+            }
+        });
+        assert_eq!(sum.get(), 3);
+    }
+}
@@ -1,4 +1,12 @@
-use objc_util::class;
+#![cfg(all(
+    any(target_vendor = "apple", feature = "gnustep"),
+    not(feature = "mock")
+))]
+
+use objc_util::{class, class_opt, runtime, Id, NSUInteger, WeakId};
+#[cfg(feature = "gnustep")]
+use objc_util::{NSOperatingSystemVersion, OS_VERSION};
+use std::{cell::Cell, os::raw::c_int, rc::Rc};
 use test_lib::*;
 
 #[test]
@@ -6,19 +14,162 @@ fn nsdata() {
     unsafe {
         let data = "aaaaa";
         let obj = nsobj_alloc(class!(NSData));
-        let obj = nsdata_init_with_bytes(obj, data.as_ptr() as _, data.len() as _);
-        let obj2 = nsdata_data_with_bytes(class!(NSData), data.as_ptr() as _, data.len() as _);
-        assert_eq!(nsobj_hash(obj), nsobj_hash(obj2));
-        assert!(match nsobj_is_equal(obj, obj2) {
-            objc_util::runtime::YES => true,
-            _ => false,
-        });
+        let obj = nsdata_init_with_bytes(obj, data.as_ptr() as _, data.len() as _).unwrap();
+        let obj2 = nsdata_data_with_bytes(data.as_ptr() as _, data.len() as _);
+        assert_eq!(nsobj_hash(&*obj), nsobj_hash(&*obj2));
+        assert!(nsobj_is_equal(&*obj, &*obj2));
 
         let data2 = "bbbbb";
-        let obj3 = nsdata_data_with_bytes(class!(NSData), data2.as_ptr() as _, data2.len() as _);
-        assert!(match nsobj_is_equal(obj, obj3) {
-            objc_util::runtime::NO => true,
-            _ => false,
-        })
+        let obj3 = nsdata_data_with_bytes(data2.as_ptr() as _, data2.len() as _);
+        assert!(!nsobj_is_equal(&*obj, &*obj3));
+    }
+}
+
+#[test]
+fn nsstring_format() {
+    unsafe {
+        let format = nsstring_with_utf8_string(b"%d %.1f %@\0".as_ptr() as _).unwrap();
+        let arg = nsstring_with_utf8_string(b"abc\0".as_ptr() as _).unwrap();
+        let string = nsstring_with_format(&*format, (42 as c_int, 0.5f64, Id::as_ptr(&arg)));
+        let expected = nsstring_with_utf8_string(b"42 0.5 abc\0".as_ptr() as _).unwrap();
+        assert!(nsobj_is_equal(&*string, &*expected));
+    }
+}
+
+#[test]
+fn nsarray_enumerate() {
+    unsafe {
+        let object = nsstring_with_utf8_string(b"abc\0".as_ptr() as _).unwrap();
+        let array = nsarray_array_with_object(&*object).as_ptr();
+        let expected = Id::as_ptr(&object) as *const runtime::Object;
+        let count = Rc::new(Cell::new(0));
+        nsarray_enumerate_objects(array, {
+            let count = count.clone();
+            move |obj, idx, _| {
+                assert_eq!(obj, expected);
+                assert_eq!(idx, count.get());
+                count.set(count.get() + 1);
+            }
+        });
+        assert_eq!(count.get(), 1);
+    }
+}
+
+#[test]
+fn nsnumber_bool() {
+    unsafe {
+        assert!(nsnumber_bool_value(nsnumber_with_bool(true)));
+        assert!(!nsnumber_bool_value(nsnumber_with_bool(false)));
+    }
+}
+
+#[test]
+fn class_lookup() {
+    let dynamic = class!(runtime NSObject) as *const runtime::Class;
+    assert_eq!(dynamic, class!(NSObject) as *const _);
+    #[cfg(target_vendor = "apple")]
+    assert_eq!(dynamic, class!(static NSObject) as *const _);
+}
+
+#[test]
+fn optional_classes() {
+    let nsobject = class!(NSObject) as *const runtime::Class;
+    assert_eq!(class_opt!(NSObject).unwrap() as *const _, nsobject);
+    assert!(class_opt!(NSObjectThatDoesNotExist).is_none());
+    assert!(class!(NSObjectThatDoesNotExist, macos = "10.0", ios = "2.0").is_none());
+}
+
+#[test]
+fn weak_id() {
+    unsafe {
+        let obj = nsobj_init(nsobj_alloc(class!(NSObject))).unwrap();
+        let weak = WeakId::new(&obj);
+        assert_eq!(weak.load().as_ref().map(Id::as_ptr), Some(Id::as_ptr(&obj)));
+        drop(obj);
+        assert!(weak.load().is_none());
+    }
+}
+
+#[test]
+fn nsdata_files() {
+    unsafe {
+        let path = std::env::temp_dir().join("objc-util-nsdata-files");
+        let path = format!("{}\0", path.display());
+        let path = nsstring_with_utf8_string(path.as_ptr() as _).unwrap();
+        let data = "aaaaa";
+        let data = nsdata_data_with_bytes(data.as_ptr() as _, data.len() as _);
+        nsdata_write_to_file(&*data, &*path, 0).unwrap();
+        let read = nsdata_data_with_contents_of_file(&*path, 0).unwrap();
+        assert!(nsobj_is_equal(&*data, &*read));
+
+        let missing = nsstring_with_utf8_string(b"/nonexistent/objc-util\0".as_ptr() as _).unwrap();
+        assert!(nsdata_data_with_contents_of_file(&*missing, 0).is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "exception")]
+fn nsarray_out_of_bounds() {
+    unsafe {
+        let object = nsstring_with_utf8_string(b"abc\0".as_ptr() as _).unwrap();
+        let array = nsarray_array_with_object(&*object).as_ptr();
+        assert_eq!(
+            nsarray_object_at_index(array, 0).ok(),
+            Some(Id::as_ptr(&object))
+        );
+
+        let exception = nsarray_object_at_index(array, 1).unwrap_err();
+        let name = exception.name().unwrap();
+        let expected = nsstring_with_utf8_string(b"NSRangeException\0".as_ptr() as _).unwrap();
+        assert!(nsobj_is_equal(&*name, &*expected));
+    }
+}
+
+#[test]
+#[cfg(feature = "exception")]
+fn exceptions_from_super_and_variadic_messages() {
+    unsafe {
+        let object = nsstring_with_utf8_string(b"abc\0".as_ptr() as _).unwrap();
+        let array = nsarray_array_with_object(&*object).as_ptr();
+        // `NSArray` itself leaves `objectAtIndex:` to its subclasses.
+        let exception = nsarray_super_object_at_index(array, 0).unwrap_err();
+        let name = exception.name().unwrap();
+        let expected =
+            nsstring_with_utf8_string(b"NSInvalidArgumentException\0".as_ptr() as _).unwrap();
+        assert!(nsobj_is_equal(&*name, &*expected));
+
+        let format = nsstring_with_utf8_string(b"%d\0".as_ptr() as _).unwrap();
+        assert!(nsstring_try_with_format(&*format, (1 as c_int,)).is_ok());
+        assert!(nsstring_try_with_format(std::ptr::null(), ()).is_err());
+    }
+}
+
+#[test]
+fn super_messages() {
+    unsafe {
+        let data = "aaaaa";
+        let obj = nsdata_data_with_bytes(data.as_ptr() as _, data.len() as _);
+        let hash = nsobj_super_hash(&*obj, class!(NSObject));
+        assert_eq!(hash, Id::as_ptr(&obj) as NSUInteger);
+    }
+}
+
+#[cfg(feature = "gnustep")]
+#[test]
+fn gnustep_os_version() {
+    unsafe {
+        let process_info = nsprocess_info_process_info();
+        let sel = runtime::Sel::register("operatingSystemVersion");
+        // gnustep-base releases without `operatingSystemVersion` report 0.0.0.
+        let expected = if nsobj_responds_to_selector(process_info, sel) {
+            nsprocess_info_operating_system_version(process_info)
+        } else {
+            NSOperatingSystemVersion {
+                major: 0,
+                minor: 0,
+                patch: 0,
+            }
+        };
+        assert_eq!(*OS_VERSION, expected);
     }
 }